@@ -0,0 +1,344 @@
+use std::{path::Path, sync::Arc};
+
+use mlua::prelude::*;
+
+use futures_util::{stream, Future};
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::{
+    multipart::{Form, Part},
+    Body, RequestBuilder, Response,
+};
+use tokio::{
+    fs::File,
+    io::AsyncReadExt,
+    sync::{mpsc, Mutex as AsyncMutex},
+};
+
+use crate::lune::{
+    builtins::serde::compress_decompress::{CompressDecompressFormat, CompressDecompressStream},
+    util::TableBuilder,
+};
+
+use super::config::{MultipartContent, MultipartPart, RequestBody};
+
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+const CHUNK_CHANNEL_SIZE: usize = 4;
+const DEFAULT_FILE_CONTENT_TYPE: &str = "application/octet-stream";
+
+const RESPONSE_BODY_IMPL_LUA: &str = r#"
+return freeze({
+	read = function(_, ...)
+		return read(body, ...)
+	end,
+	readAll = function(_, ...)
+		return read_all(body, ...)
+	end,
+	readChunks = function(_, callback, amount)
+		while true do
+			local chunk = read(body, amount)
+			if chunk == nil then
+				break
+			end
+			callback(chunk)
+		end
+	end,
+})
+"#;
+
+type BodyChunkResult = Result<Vec<u8>, std::io::Error>;
+
+/**
+    Sets the body of the given request.
+
+    Bodies that are read from files or chunk iterators are streamed, meaning
+    that they never need to be fully loaded into memory, but these also can
+    not be cloned, so requests using them will never be retried. The same
+    goes for multipart forms, since those are always sent as streams.
+
+    Form bodies set the content type header, unless one was already set.
+
+    Returns the request along with a future that must be polled alongside
+    sending the request, which feeds chunks from a chunk iterator into the body.
+*/
+pub async fn set_request_body<'lua>(
+    request: RequestBuilder,
+    body: Option<RequestBody<'lua>>,
+    has_content_type: bool,
+) -> LuaResult<(RequestBuilder, impl Future<Output = LuaResult<()>> + 'lua)> {
+    let mut chunks = None;
+    let request = match body {
+        None => request.body(Vec::new()),
+        Some(RequestBody::Bytes(bytes)) => request.body(bytes),
+        Some(RequestBody::File(path)) => {
+            let (body, len) = open_file_body(&path).await?;
+            request.header(CONTENT_LENGTH, len).body(body)
+        }
+        Some(RequestBody::Form(fields)) => {
+            // NOTE: Fields are encoded the same way as net.urlEncode encodes strings
+            let encoded = fields
+                .iter()
+                .map(|(name, value)| {
+                    format!(
+                        "{}={}",
+                        urlencoding::encode(name),
+                        urlencoding::encode_binary(value)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            let request = if has_content_type {
+                request
+            } else {
+                request.header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            };
+            request.body(encoded)
+        }
+        Some(RequestBody::Multipart(parts)) => {
+            let mut form = Form::new();
+            for part in parts {
+                let name = part.name.clone();
+                form = form.part(name, create_multipart_part(part).await?);
+            }
+            request.multipart(form)
+        }
+        Some(RequestBody::Chunks(func)) => {
+            let (tx, rx) = mpsc::channel::<BodyChunkResult>(CHUNK_CHANNEL_SIZE);
+            let stream = stream::unfold(rx, |mut rx| async move {
+                rx.recv().await.map(|chunk| (chunk, rx))
+            });
+            chunks = Some((func, tx));
+            request.body(Body::wrap_stream(stream))
+        }
+    };
+    let feed = async move {
+        let Some((func, tx)) = chunks else {
+            return Ok(());
+        };
+        loop {
+            let chunk = match func.call::<_, Option<LuaString>>(()) {
+                Ok(Some(chunk)) => Ok(chunk.as_bytes().to_vec()),
+                Ok(None) => break,
+                Err(e) => Err(e),
+            };
+            match chunk {
+                Ok(chunk) => {
+                    // NOTE: The receiver is dropped if the request failed,
+                    // which will already have returned an error elsewhere
+                    if tx.send(Ok(chunk)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let message = format!("Request body chunk iterator errored - {e}");
+                    tx.send(Err(std::io::Error::other(message.clone())))
+                        .await
+                        .ok();
+                    return Err(LuaError::RuntimeError(message));
+                }
+            }
+        }
+        Ok(())
+    };
+    Ok((request, feed))
+}
+
+async fn open_file_body(path: &Path) -> LuaResult<(Body, u64)> {
+    let file = File::open(path).await.map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to open request body file '{}' - {e}",
+            path.display()
+        ))
+    })?;
+    let len = file.metadata().await?.len();
+    let stream = stream::unfold(file, |mut file| async move {
+        let mut buffer = vec![0; FILE_CHUNK_SIZE];
+        match file.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buffer.truncate(n);
+                Some((Ok::<_, std::io::Error>(buffer), file))
+            }
+            Err(e) => Some((Err(e), file)),
+        }
+    });
+    Ok((Body::wrap_stream(stream), len))
+}
+
+async fn create_multipart_part(part: MultipartPart) -> LuaResult<Part> {
+    let MultipartPart {
+        name,
+        content,
+        filename,
+        content_type,
+    } = part;
+    let mut result = match content {
+        MultipartContent::Bytes(bytes) => Part::bytes(bytes),
+        MultipartContent::File(path) => {
+            let (body, len) = open_file_body(&path).await?;
+            Part::stream_with_length(body, len)
+        }
+    };
+    // NOTE: Parts with filenames are files, which should always have a content type
+    let content_type = match (content_type, &filename) {
+        (Some(content_type), _) => Some(content_type),
+        (None, Some(_)) => Some(DEFAULT_FILE_CONTENT_TYPE.to_string()),
+        (None, None) => None,
+    };
+    if let Some(content_type) = content_type {
+        result = result.mime_str(&content_type).map_err(|e| {
+            LuaError::RuntimeError(format!(
+                "Invalid content type '{content_type}' for multipart field '{name}' - {e}"
+            ))
+        })?;
+    }
+    if let Some(filename) = filename {
+        result = result.file_name(filename);
+    }
+    Ok(result)
+}
+
+/**
+    A reader for the body of a response, which
+    decompresses the body while reading, if needed.
+*/
+pub struct ResponseBodyReader {
+    response: Response,
+    decoder: Option<CompressDecompressStream>,
+    buffer: Vec<u8>,
+    received: u64,
+    finished: bool,
+}
+
+impl ResponseBodyReader {
+    pub fn new(response: Response, format: Option<CompressDecompressFormat>) -> LuaResult<Self> {
+        Ok(Self {
+            response,
+            decoder: format
+                .map(CompressDecompressStream::decompressor)
+                .transpose()?,
+            buffer: Vec::new(),
+            received: 0,
+            finished: false,
+        })
+    }
+
+    /**
+        The amount of bytes received so far, before any decompression.
+    */
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /**
+        Reads the next chunk of the body, in whatever size it was
+        received in, returning `None` once the full body has been read.
+    */
+    pub async fn next_chunk(&mut self) -> LuaResult<Option<Vec<u8>>> {
+        if !self.buffer.is_empty() {
+            return Ok(Some(std::mem::take(&mut self.buffer)));
+        }
+        while !self.finished {
+            let chunk = match self.response.chunk().await.into_lua_err()? {
+                Some(chunk) => {
+                    self.received += chunk.len() as u64;
+                    match &mut self.decoder {
+                        Some(decoder) => decoder.write(&chunk)?,
+                        None => chunk.to_vec(),
+                    }
+                }
+                None => {
+                    self.finished = true;
+                    match &mut self.decoder {
+                        Some(decoder) => decoder.finish()?,
+                        None => Vec::new(),
+                    }
+                }
+            };
+            // NOTE: Decoders may need more input before they can output
+            // anything, so we skip any empty chunks and keep on reading
+            if !chunk.is_empty() {
+                return Ok(Some(chunk));
+            }
+        }
+        Ok(None)
+    }
+
+    /**
+        Reads at most `max` bytes of the body, waiting for at least one byte to
+        be received, returning `None` once the full body has been read.
+    */
+    pub async fn read(&mut self, max: Option<usize>) -> LuaResult<Option<Vec<u8>>> {
+        let Some(mut chunk) = self.next_chunk().await? else {
+            return Ok(None);
+        };
+        if let Some(max) = max {
+            if chunk.len() > max {
+                self.buffer = chunk.split_off(max);
+            }
+        }
+        Ok(Some(chunk))
+    }
+
+    /**
+        Reads the remaining body, returning an empty
+        buffer if the full body has already been read.
+    */
+    pub async fn read_to_end(&mut self) -> LuaResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = self.next_chunk().await? {
+            bytes.extend(chunk);
+        }
+        Ok(bytes)
+    }
+}
+
+#[derive(Clone)]
+pub struct NetResponseBody(Arc<AsyncMutex<ResponseBodyReader>>);
+
+impl NetResponseBody {
+    pub fn new(reader: ResponseBodyReader) -> Self {
+        Self(Arc::new(AsyncMutex::new(reader)))
+    }
+
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable<'static>> {
+        let table_freeze = lua
+            .globals()
+            .get::<_, LuaTable>("table")?
+            .get::<_, LuaFunction>("freeze")?;
+        let body_env = TableBuilder::new(lua)?
+            .with_value("body", self)?
+            .with_async_function("read", response_body_read)?
+            .with_async_function("read_all", response_body_read_all)?
+            .with_value("freeze", table_freeze)?
+            .build_readonly()?;
+        lua.load(RESPONSE_BODY_IMPL_LUA)
+            .set_name("body")
+            .set_environment(body_env)
+            .eval()
+    }
+}
+
+impl LuaUserData for NetResponseBody {}
+
+async fn response_body_read<'lua>(
+    lua: &'lua Lua,
+    (body, max): (LuaUserDataRef<'lua, NetResponseBody>, Option<usize>),
+) -> LuaResult<Option<LuaString<'lua>>> {
+    if max == Some(0) {
+        return Err(LuaError::RuntimeError(
+            "Amount of bytes to read must be greater than zero".to_string(),
+        ));
+    }
+    let reader = Arc::clone(&body.0);
+    let chunk = reader.lock().await.read(max).await?;
+    chunk.map(|chunk| lua.create_string(chunk)).transpose()
+}
+
+async fn response_body_read_all<'lua>(
+    lua: &'lua Lua,
+    body: LuaUserDataRef<'lua, NetResponseBody>,
+) -> LuaResult<LuaString<'lua>> {
+    let reader = Arc::clone(&body.0);
+    let bytes = reader.lock().await.read_to_end().await?;
+    lua.create_string(bytes)
+}
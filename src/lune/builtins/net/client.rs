@@ -1,20 +1,51 @@
-use std::str::FromStr;
+use std::{
+    str::FromStr,
+    sync::{Arc, MutexGuard},
+    time::Duration,
+};
 
 use mlua::prelude::*;
 
 use hyper::{header::HeaderName, http::HeaderValue, HeaderMap};
-use reqwest::{IntoUrl, Method, RequestBuilder};
+use reqwest::{redirect::Policy as RedirectPolicy, Method, Proxy, RequestBuilder, Url};
+use reqwest_cookie_store::{CookieStore, CookieStoreMutex};
+use tokio::fs;
+
+use crate::lune::util::TableBuilder;
+
+use super::{config::RequestConfig, send_request};
 
 const REGISTRY_KEY: &str = "NetClient";
 
+const NET_CLIENT_IMPL_LUA: &str = r#"
+return freeze({
+	request = function(_, ...)
+		return request(client, ...)
+	end,
+	saveCookies = function(_, ...)
+		return save_cookies(client, ...)
+	end,
+	loadCookies = function(_, ...)
+		return load_cookies(client, ...)
+	end,
+	clearCookies = function(_, ...)
+		return clear_cookies(client, ...)
+	end,
+})
+"#;
+
 pub struct NetClientBuilder {
     builder: reqwest::ClientBuilder,
+    base_url: Option<Url>,
+    cookies: Option<Arc<CookieStoreMutex>>,
 }
 
 impl NetClientBuilder {
     pub fn new() -> NetClientBuilder {
         Self {
             builder: reqwest::ClientBuilder::new(),
+            base_url: None,
+            cookies: None,
         }
     }
 
@@ -33,18 +64,163 @@ impl NetClientBuilder {
         Ok(self)
     }
 
+    pub fn base_url(mut self, base_url: Option<&str>) -> LuaResult<Self> {
+        if let Some(base_url) = base_url {
+            let mut url = Url::parse(base_url).map_err(|e| {
+                LuaError::RuntimeError(format!("Invalid base url '{base_url}' - {e}"))
+            })?;
+            if url.cannot_be_a_base() {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid base url '{base_url}' - url can not be used as a base"
+                )));
+            }
+            // NOTE: Joining urls replaces the last path segment unless the
+            // base path ends with a slash, so we make sure that it always does
+            if !url.path().ends_with('/') {
+                url.set_path(&format!("{}/", url.path()));
+            }
+            self.base_url = Some(url);
+        }
+        Ok(self)
+    }
+
+    pub fn cookies(mut self, enabled: bool) -> Self {
+        if enabled {
+            let store = Arc::new(CookieStoreMutex::default());
+            self.builder = self.builder.cookie_provider(Arc::clone(&store));
+            self.cookies = Some(store);
+        }
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        if let Some(timeout) = timeout {
+            self.builder = self.builder.timeout(timeout);
+        }
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        if let Some(timeout) = timeout {
+            self.builder = self.builder.connect_timeout(timeout);
+        }
+        self
+    }
+
+    pub fn redirects(mut self, max_redirects: Option<usize>) -> Self {
+        self.builder = self.builder.redirect(match max_redirects {
+            Some(0) => RedirectPolicy::none(),
+            Some(max) => RedirectPolicy::limited(max),
+            None => RedirectPolicy::default(),
+        });
+        self
+    }
+
+    pub fn proxy(mut self, proxy: Option<&str>) -> LuaResult<Self> {
+        if let Some(proxy) = proxy {
+            let proxy = Proxy::all(proxy).map_err(|e| {
+                LuaError::RuntimeError(format!("Invalid proxy url '{proxy}' - {e}"))
+            })?;
+            self.builder = self.builder.proxy(proxy);
+        }
+        Ok(self)
+    }
+
     pub fn build(self) -> LuaResult<NetClient> {
         let client = self.builder.build().into_lua_err()?;
-        Ok(NetClient(client))
+        Ok(NetClient {
+            inner: client,
+            base_url: self.base_url,
+            cookies: self.cookies,
+        })
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct NetClient(reqwest::Client);
+pub struct NetClient {
+    inner: reqwest::Client,
+    base_url: Option<Url>,
+    cookies: Option<Arc<CookieStoreMutex>>,
+}
 
 impl NetClient {
-    pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
-        self.0.request(method, url)
+    /**
+        Creates a new request, resolving the given url against
+        the base url of the client if it is a relative url.
+    */
+    pub fn request(&self, method: Method, url: &str) -> LuaResult<RequestBuilder> {
+        let url = match (&self.base_url, Url::parse(url)) {
+            (_, Ok(url)) => url,
+            (Some(base_url), Err(_)) => base_url
+                .join(url.trim_start_matches('/'))
+                .map_err(|e| LuaError::RuntimeError(format!("Invalid url '{url}' - {e}")))?,
+            (None, Err(e)) => {
+                return Err(LuaError::RuntimeError(format!("Invalid url '{url}' - {e}")))
+            }
+        };
+        Ok(self.inner.request(method, url))
+    }
+
+    /**
+        Serializes all cookies in the cookie jar of this client into json
+        lines, including session cookies. Expired cookies are skipped when loading.
+
+        Returns `None` if this client does not store cookies.
+    */
+    pub fn save_cookies(&self) -> LuaResult<Option<Vec<u8>>> {
+        let Some(cookies) = &self.cookies else {
+            return Ok(None);
+        };
+        let mut bytes = Vec::new();
+        lock_cookies(cookies)?
+            .save_incl_expired_and_nonpersistent_json(&mut bytes)
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to save cookies - {e}")))?;
+        Ok(Some(bytes))
+    }
+
+    /**
+        Replaces all cookies in the cookie jar of this client with cookies
+        deserialized from json lines, skipping any cookies that have expired.
+
+        Returns `false` if this client does not store cookies.
+    */
+    pub fn load_cookies(&self, bytes: &[u8]) -> LuaResult<bool> {
+        let Some(cookies) = &self.cookies else {
+            return Ok(false);
+        };
+        let loaded = CookieStore::load_json(bytes)
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to load cookies - {e}")))?;
+        *lock_cookies(cookies)? = loaded;
+        Ok(true)
+    }
+
+    /**
+        Removes all cookies from the cookie jar of this client.
+    */
+    pub fn clear_cookies(&self) -> LuaResult<()> {
+        if let Some(cookies) = &self.cookies {
+            lock_cookies(cookies)?.clear();
+        }
+        Ok(())
+    }
+
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable<'static>> {
+        let table_freeze = lua
+            .globals()
+            .get::<_, LuaTable>("table")?
+            .get::<_, LuaFunction>("freeze")?;
+        let client_env = TableBuilder::new(lua)?
+            .with_value("client", self)?
+            .with_async_function("request", client_request)?
+            .with_async_function("save_cookies", client_save_cookies)?
+            .with_async_function("load_cookies", client_load_cookies)?
+            .with_function("clear_cookies", client_clear_cookies)?
+            .with_value("freeze", table_freeze)?
+            .build_readonly()?;
+        lua.load(NET_CLIENT_IMPL_LUA)
+            .set_name("client")
+            .set_environment(client_env)
+            .eval()
     }
 
     pub fn into_registry(self, lua: &Lua) {
@@ -78,3 +254,65 @@ impl<'lua> From<&'lua Lua> for NetClient {
             .expect("Missing require context in lua registry")
     }
 }
+
+async fn client_request<'lua>(
+    lua: &'lua Lua,
+    (client, config): (LuaUserDataRef<'lua, NetClient>, RequestConfig<'lua>),
+) -> LuaResult<LuaTable<'lua>>
+where
+    'lua: 'static, // FIXME: Get rid of static lifetime bound here
+{
+    if config.options.needs_own_client() {
+        return Err(LuaError::RuntimeError(
+            "The 'connectTimeout', 'redirects' and 'proxy' options can not be \
+            set for requests sent using a client, set them on the client instead"
+                .to_string(),
+        ));
+    }
+    let client = client.clone();
+    send_request(lua, &client, config).await
+}
+
+async fn client_save_cookies<'lua>(
+    _: &'lua Lua,
+    (client, path): (LuaUserDataRef<'lua, NetClient>, String),
+) -> LuaResult<()> {
+    let Some(bytes) = client.save_cookies()? else {
+        return Err(cookies_disabled_error());
+    };
+    fs::write(&path, bytes).await.into_lua_err()
+}
+
+async fn client_load_cookies<'lua>(
+    _: &'lua Lua,
+    (client, path): (LuaUserDataRef<'lua, NetClient>, String),
+) -> LuaResult<()> {
+    let client = client.clone();
+    let bytes = fs::read(&path).await.into_lua_err()?;
+    if client.load_cookies(&bytes)? {
+        Ok(())
+    } else {
+        Err(cookies_disabled_error())
+    }
+}
+
+fn client_clear_cookies<'lua>(
+    _: &'lua Lua,
+    client: LuaUserDataRef<'lua, NetClient>,
+) -> LuaResult<()> {
+    client.clear_cookies()
+}
+
+fn lock_cookies(cookies: &CookieStoreMutex) -> LuaResult<MutexGuard<'_, CookieStore>> {
+    cookies.lock().map_err(|_| {
+        LuaError::RuntimeError(
+            "Failed to access cookies, a previous access to them panicked".to_string(),
+        )
+    })
+}
+
+fn cookies_disabled_error() -> LuaError {
+    LuaError::RuntimeError(
+        "Cookies are not enabled for this client, set 'cookies' to true to enable them".to_string(),
+    )
+}
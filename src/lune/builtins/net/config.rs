@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
 use mlua::prelude::*;
 
@@ -6,14 +6,47 @@ use reqwest::Method;
 
 // Net request config
 
+const REQUEST_OPTIONS: &str = "request config options";
+
+// NOTE: Retries are limited so that a request can not end up waiting for hours,
+// the delay between retries keeps doubling but never grows larger than the maximum
+pub const MAX_RETRIES: u32 = 10;
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Clone)]
 pub struct RequestConfigOptions {
     pub decompress: bool,
+    pub stream: bool,
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub redirects: Option<usize>,
+    pub proxy: Option<String>,
+    pub retries: u32,
+    pub retry_delay: Duration,
+}
+
+impl RequestConfigOptions {
+    /**
+        If any of the options can only be set on a client, meaning
+        that a separate client must be created to send the request.
+    */
+    pub fn needs_own_client(&self) -> bool {
+        self.connect_timeout.is_some() || self.redirects.is_some() || self.proxy.is_some()
+    }
 }
 
 impl Default for RequestConfigOptions {
     fn default() -> Self {
-        Self { decompress: true }
+        Self {
+            decompress: true,
+            stream: false,
+            timeout: None,
+            connect_timeout: None,
+            redirects: None,
+            proxy: None,
+            retries: 0,
+            retry_delay: Duration::from_millis(500),
+        }
     }
 }
 
@@ -30,7 +63,47 @@ impl<'lua> FromLua<'lua> for RequestConfigOptions {
                     "Invalid option value for 'decompress' in request config options".to_string(),
                 )),
             }?;
-            return Ok(Self { decompress });
+            let stream = match tab.raw_get::<_, Option<bool>>("stream") {
+                Ok(stream) => Ok(stream.unwrap_or_default()),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'stream' in request config options".to_string(),
+                )),
+            }?;
+            // Extract timeouts, given in seconds
+            let timeout = get_duration(&tab, "timeout", REQUEST_OPTIONS)?;
+            let connect_timeout = get_duration(&tab, "connectTimeout", REQUEST_OPTIONS)?;
+            // Extract redirects, which may be a maximum amount or false for no redirects
+            let redirects = get_redirects(&tab, REQUEST_OPTIONS)?;
+            // Extract proxy
+            let proxy = get_proxy(&tab, REQUEST_OPTIONS)?;
+            // Extract retries and the delay before the first retry
+            let retries = match tab.raw_get::<_, Option<u32>>("retries") {
+                Ok(retries) if retries.unwrap_or_default() <= MAX_RETRIES => {
+                    Ok(retries.unwrap_or_default())
+                }
+                _ => Err(LuaError::RuntimeError(format!(
+                    "Invalid option value for 'retries' in request config options \
+                    - expected an integer between 0 and {MAX_RETRIES}"
+                ))),
+            }?;
+            let retry_delay = match get_duration(&tab, "retryDelay", REQUEST_OPTIONS)? {
+                Some(delay) if delay > MAX_RETRY_DELAY => Err(LuaError::RuntimeError(format!(
+                    "Invalid option value for 'retryDelay' in request config options \
+                    - expected at most {} seconds",
+                    MAX_RETRY_DELAY.as_secs()
+                ))),
+                delay => Ok(delay.unwrap_or_else(|| Self::default().retry_delay)),
+            }?;
+            return Ok(Self {
+                decompress,
+                stream,
+                timeout,
+                connect_timeout,
+                redirects,
+                proxy,
+                retries,
+                retry_delay,
+            });
         }
         // Anything else is invalid
         Err(LuaError::FromLuaConversionError {
@@ -44,13 +117,243 @@ impl<'lua> FromLua<'lua> for RequestConfigOptions {
     }
 }
 
+// NOTE: Methods are case-sensitive, so only the standard methods are
+// uppercased, and extension methods are always kept exactly as given
+const STANDARD_METHODS: &[Method] = &[
+    Method::GET,
+    Method::HEAD,
+    Method::POST,
+    Method::PUT,
+    Method::DELETE,
+    Method::CONNECT,
+    Method::OPTIONS,
+    Method::TRACE,
+    Method::PATCH,
+];
+
+/**
+    Uppercases the given method if it is one of the standard
+    methods, and returns any other method unchanged.
+*/
+pub fn normalize_method(method: &str) -> String {
+    let uppercase = method.to_ascii_uppercase();
+    if STANDARD_METHODS.iter().any(|m| m.as_str() == uppercase) {
+        uppercase
+    } else {
+        method.to_string()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RequestAuth {
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer(String),
+}
+
+impl<'lua> FromLua<'lua> for RequestAuth {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::Table(tab) = &value {
+            let username = tab.raw_get::<_, Option<String>>("username")?;
+            let password = tab.raw_get::<_, Option<String>>("password")?;
+            let bearer = tab.raw_get::<_, Option<String>>("bearer")?;
+            match (username, bearer) {
+                (Some(username), None) => return Ok(Self::Basic { username, password }),
+                (None, Some(token)) => return Ok(Self::Bearer(token)),
+                _ => {}
+            }
+        }
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "RequestAuth",
+            message: Some(
+                "Invalid request auth - expected a table with either \
+                'username' and 'password', or 'bearer'"
+                    .to_string(),
+            ),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RequestBody<'lua> {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+    Chunks(LuaFunction<'lua>),
+    Form(Vec<(String, Vec<u8>)>),
+    Multipart(Vec<MultipartPart>),
+}
+
+#[derive(Debug, Clone)]
+pub enum MultipartContent {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct MultipartPart {
+    pub name: String,
+    pub content: MultipartContent,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+}
+
+impl MultipartPart {
+    fn from_lua_table(name: String, tab: &LuaTable) -> LuaResult<Self> {
+        let content = tab.raw_get::<_, Option<LuaString>>("content")?;
+        let file = tab.raw_get::<_, Option<String>>("file")?;
+        let content = match (content, file) {
+            (Some(content), None) => MultipartContent::Bytes(content.as_bytes().to_vec()),
+            (None, Some(file)) => MultipartContent::File(PathBuf::from(file)),
+            _ => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid multipart file '{name}' - expected a table \
+                    with either a 'content' string or a 'file' path"
+                )))
+            }
+        };
+        // NOTE: Parts read from files are named after the file by default
+        let filename = match (tab.raw_get::<_, Option<String>>("filename")?, &content) {
+            (Some(filename), _) => Some(filename),
+            (None, MultipartContent::File(path)) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string()),
+            (None, MultipartContent::Bytes(_)) => None,
+        };
+        Ok(Self {
+            name,
+            content,
+            filename,
+            content_type: tab.raw_get("contentType")?,
+        })
+    }
+}
+
+impl<'lua> FromLua<'lua> for RequestBody<'lua> {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            // Functions are iterators that return chunks of the body, until they return nil
+            LuaValue::Function(f) => Ok(Self::Chunks(f)),
+            // Tables describe where to read the body from, or a form to encode
+            LuaValue::Table(tab) => {
+                if let Some(path) = tab.raw_get::<_, Option<String>>("file")? {
+                    Ok(Self::File(PathBuf::from(path)))
+                } else if let Some(form) = tab.raw_get::<_, Option<LuaTable>>("form")? {
+                    let mut fields = Vec::new();
+                    for (name, value) in sorted_form_fields(form)? {
+                        for value in form_field_values(&name, value, lua)? {
+                            fields.push((name.clone(), value));
+                        }
+                    }
+                    Ok(Self::Form(fields))
+                } else if let Some(form) = tab.raw_get::<_, Option<LuaTable>>("multipart")? {
+                    let mut parts = Vec::new();
+                    for (name, value) in sorted_form_fields(form)? {
+                        parts.extend(multipart_field_parts(name, value, lua)?);
+                    }
+                    Ok(Self::Multipart(parts))
+                } else {
+                    Err(LuaError::RuntimeError(
+                        "Invalid request body - expected a table with \
+                        a 'file' path, a 'form' table, or a 'multipart' table"
+                            .to_string(),
+                    ))
+                }
+            }
+            // Anything else should be a string, or something that can be converted into one
+            value => match LuaString::from_lua(value, lua) {
+                Ok(s) => Ok(Self::Bytes(s.as_bytes().to_vec())),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid request body - expected a string, a table with a 'file' \
+                    path or form fields, or a function returning chunks"
+                        .to_string(),
+                )),
+            },
+        }
+    }
+}
+
+/**
+    Gets the fields of a form, sorted by name so that encoded forms are deterministic.
+*/
+fn sorted_form_fields(form: LuaTable) -> LuaResult<Vec<(String, LuaValue)>> {
+    let mut fields = form
+        .pairs::<String, LuaValue>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(fields)
+}
+
+/**
+    Gets the values of a form field, which may be a single
+    value or an array of values for repeated fields.
+*/
+fn form_field_values<'lua>(
+    name: &str,
+    value: LuaValue<'lua>,
+    lua: &'lua Lua,
+) -> LuaResult<Vec<Vec<u8>>> {
+    let values = match value {
+        LuaValue::Table(tab) => tab
+            .sequence_values::<LuaValue>()
+            .collect::<LuaResult<Vec<_>>>()?,
+        value => vec![value],
+    };
+    values
+        .into_iter()
+        .map(|value| match LuaString::from_lua(value, lua) {
+            Ok(s) => Ok(s.as_bytes().to_vec()),
+            Err(_) => Err(LuaError::RuntimeError(format!(
+                "Invalid form field '{name}' - expected a string or an array of strings"
+            ))),
+        })
+        .collect()
+}
+
+/**
+    Gets the parts for a multipart form field, which may be a single text value or
+    file, or an array of text values and / or files for repeated fields.
+*/
+fn multipart_field_parts<'lua>(
+    name: String,
+    value: LuaValue<'lua>,
+    lua: &'lua Lua,
+) -> LuaResult<Vec<MultipartPart>> {
+    let values = match value {
+        LuaValue::Table(tab) if tab.raw_len() > 0 => tab
+            .sequence_values::<LuaValue>()
+            .collect::<LuaResult<Vec<_>>>()?,
+        value => vec![value],
+    };
+    values
+        .into_iter()
+        .map(|value| match value {
+            LuaValue::Table(tab) => MultipartPart::from_lua_table(name.clone(), &tab),
+            value => match LuaString::from_lua(value, lua) {
+                Ok(s) => Ok(MultipartPart {
+                    name: name.clone(),
+                    content: MultipartContent::Bytes(s.as_bytes().to_vec()),
+                    filename: None,
+                    content_type: None,
+                }),
+                Err(_) => Err(LuaError::RuntimeError(format!(
+                    "Invalid multipart field '{name}' - expected a string, a file, or an array of them"
+                ))),
+            },
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone)]
 pub struct RequestConfig<'a> {
     pub url: String,
     pub method: Method,
     pub query: HashMap<LuaString<'a>, LuaString<'a>>,
     pub headers: HashMap<LuaString<'a>, LuaString<'a>>,
-    pub body: Option<Vec<u8>>,
+    pub body: Option<RequestBody<'a>>,
+    pub auth: Option<RequestAuth>,
     pub options: RequestConfigOptions,
 }
 
@@ -64,6 +367,7 @@ impl<'lua> FromLua<'lua> for RequestConfig<'lua> {
                 query: HashMap::new(),
                 headers: HashMap::new(),
                 body: None,
+                auth: None,
                 options: Default::default(),
             });
         }
@@ -78,7 +382,7 @@ impl<'lua> FromLua<'lua> for RequestConfig<'lua> {
             }?;
             // Extract method
             let method = match tab.raw_get::<_, LuaString>("method") {
-                Ok(config_method) => config_method.to_string_lossy().trim().to_ascii_uppercase(),
+                Ok(config_method) => config_method.to_string_lossy().trim().to_string(),
                 Err(_) => "GET".to_string(),
             };
             // Extract query
@@ -106,25 +410,21 @@ impl<'lua> FromLua<'lua> for RequestConfig<'lua> {
                 Err(_) => HashMap::new(),
             };
             // Extract body
-            let body = match tab.raw_get::<_, LuaString>("body") {
-                Ok(config_body) => Some(config_body.as_bytes().to_owned()),
-                Err(_) => None,
+            let body = match tab.raw_get::<_, LuaValue>("body")? {
+                LuaValue::Nil => None,
+                value => Some(RequestBody::from_lua(value, lua)?),
             };
-            // Convert method string into proper enum
-            let method = method.trim().to_ascii_uppercase();
-            let method = match method.as_ref() {
-                "GET" => Ok(Method::GET),
-                "POST" => Ok(Method::POST),
-                "PUT" => Ok(Method::PUT),
-                "DELETE" => Ok(Method::DELETE),
-                "HEAD" => Ok(Method::HEAD),
-                "OPTIONS" => Ok(Method::OPTIONS),
-                "PATCH" => Ok(Method::PATCH),
-                _ => Err(LuaError::RuntimeError(format!(
-                    "Invalid request config method '{}'",
-                    &method
-                ))),
-            }?;
+            // Extract auth
+            let auth = match tab.raw_get::<_, LuaValue>("auth")? {
+                LuaValue::Nil => None,
+                value => Some(RequestAuth::from_lua(value, lua)?),
+            };
+            // Convert method string into proper enum, any valid token is allowed
+            // here to support extension methods such as those used by WebDAV
+            let method = normalize_method(&method);
+            let method = Method::from_bytes(method.as_bytes()).map_err(|_| {
+                LuaError::RuntimeError(format!("Invalid request config method '{method}'"))
+            })?;
             // Parse any extra options given
             let options = match tab.raw_get::<_, LuaValue>("options") {
                 Ok(opts) => RequestConfigOptions::from_lua(opts, lua)?,
@@ -137,6 +437,7 @@ impl<'lua> FromLua<'lua> for RequestConfig<'lua> {
                 query,
                 headers,
                 body,
+                auth,
                 options,
             });
         };
@@ -152,6 +453,130 @@ impl<'lua> FromLua<'lua> for RequestConfig<'lua> {
     }
 }
 
+fn get_duration(tab: &LuaTable, key: &str, config: &str) -> LuaResult<Option<Duration>> {
+    match tab.raw_get::<_, Option<f64>>(key) {
+        Ok(None) => Ok(None),
+        Ok(Some(secs)) if Duration::try_from_secs_f64(secs).is_ok() => {
+            Ok(Some(Duration::from_secs_f64(secs)))
+        }
+        _ => Err(LuaError::RuntimeError(format!(
+            "Invalid option value for '{key}' in {config} \
+            - expected a non-negative number of seconds"
+        ))),
+    }
+}
+
+fn get_redirects(tab: &LuaTable, config: &str) -> LuaResult<Option<usize>> {
+    match tab.raw_get::<_, LuaValue>("redirects")? {
+        LuaValue::Nil => Ok(None),
+        LuaValue::Boolean(true) => Ok(None),
+        LuaValue::Boolean(false) => Ok(Some(0)),
+        LuaValue::Integer(i) if i >= 0 => Ok(Some(i as usize)),
+        LuaValue::Number(n) if n >= 0.0 && n.fract() == 0.0 => Ok(Some(n as usize)),
+        _ => Err(LuaError::RuntimeError(format!(
+            "Invalid option value for 'redirects' in {config} \
+            - expected a non-negative integer or boolean"
+        ))),
+    }
+}
+
+fn get_proxy(tab: &LuaTable, config: &str) -> LuaResult<Option<String>> {
+    match tab.raw_get::<_, Option<LuaString>>("proxy") {
+        Ok(proxy) => Ok(proxy.map(|p| p.to_string_lossy().to_string())),
+        Err(_) => Err(LuaError::RuntimeError(format!(
+            "Invalid option value for 'proxy' in {config}"
+        ))),
+    }
+}
+
+// Net client config
+
+const CLIENT_CONFIG: &str = "client config";
+
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub base_url: Option<String>,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub cookies: bool,
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub redirects: Option<usize>,
+    pub proxy: Option<String>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            base_url: None,
+            headers: Vec::new(),
+            cookies: true,
+            timeout: None,
+            connect_timeout: None,
+            redirects: None,
+            proxy: None,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for ClientConfig {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        // Nil means default config, table means custom config
+        if let LuaValue::Nil = value {
+            return Ok(Self::default());
+        } else if let LuaValue::Table(tab) = value {
+            // Extract base url
+            let base_url = match tab.raw_get::<_, Option<LuaString>>("baseUrl") {
+                Ok(url) => Ok(url.map(|u| u.to_string_lossy().to_string())),
+                Err(_) => Err(LuaError::RuntimeError(format!(
+                    "Invalid option value for 'baseUrl' in {CLIENT_CONFIG}"
+                ))),
+            }?;
+            // Extract default headers
+            let headers = match tab.raw_get::<_, Option<LuaTable>>("headers")? {
+                Some(config_headers) => {
+                    let mut headers = Vec::new();
+                    for pair in config_headers.pairs::<String, LuaString>() {
+                        let (key, value) = pair?;
+                        headers.push((key, value.as_bytes().to_vec()));
+                    }
+                    headers
+                }
+                None => Vec::new(),
+            };
+            // Extract cookies flag
+            let cookies = match tab.raw_get::<_, Option<bool>>("cookies") {
+                Ok(cookies) => Ok(cookies.unwrap_or(true)),
+                Err(_) => Err(LuaError::RuntimeError(format!(
+                    "Invalid option value for 'cookies' in {CLIENT_CONFIG}"
+                ))),
+            }?;
+            // Extract options that are shared with requests
+            let timeout = get_duration(&tab, "timeout", CLIENT_CONFIG)?;
+            let connect_timeout = get_duration(&tab, "connectTimeout", CLIENT_CONFIG)?;
+            let redirects = get_redirects(&tab, CLIENT_CONFIG)?;
+            let proxy = get_proxy(&tab, CLIENT_CONFIG)?;
+            return Ok(Self {
+                base_url,
+                headers,
+                cookies,
+                timeout,
+                connect_timeout,
+                redirects,
+                proxy,
+            });
+        }
+        // Anything else is invalid
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "ClientConfig",
+            message: Some(format!(
+                "Invalid client config - expected table or nil, got {}",
+                value.type_name()
+            )),
+        })
+    }
+}
+
 // Net serve config
 
 pub struct ServeConfig<'a> {
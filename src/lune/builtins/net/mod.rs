@@ -2,7 +2,16 @@ use std::collections::HashMap;
 
 use mlua::prelude::*;
 
-use hyper::header::{CONTENT_ENCODING, CONTENT_LENGTH};
+use hyper::{
+    header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE},
+    StatusCode, Version,
+};
+use reqwest::RequestBuilder;
+use tokio::{
+    fs,
+    io::AsyncWriteExt,
+    time::{self, Instant},
+};
 
 use crate::lune::{scheduler::Scheduler, util::TableBuilder};
 
@@ -13,6 +22,7 @@ use super::serde::{
     encode_decode::{EncodeDecodeConfig, EncodeDecodeFormat},
 };
 
+mod body;
 mod client;
 mod config;
 mod processing;
@@ -20,8 +30,9 @@ mod response;
 mod server;
 mod websocket;
 
+use body::{set_request_body, NetResponseBody, ResponseBodyReader};
 use client::{NetClient, NetClientBuilder};
-use config::{RequestConfig, ServeConfig};
+use config::{ClientConfig, RequestAuth, RequestConfig, ServeConfig, MAX_RETRY_DELAY};
 use server::bind_to_localhost;
 use websocket::NetWebSocket;
 
@@ -34,6 +45,8 @@ pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
         .with_function("jsonEncode", net_json_encode)?
         .with_function("jsonDecode", net_json_decode)?
         .with_async_function("request", net_request)?
+        .with_async_function("download", net_download)?
+        .with_function("client", net_client)?
         .with_async_function("socket", net_socket)?
         .with_async_function("serve", net_serve)?
         .with_function("urlEncode", net_url_encode)?
@@ -65,63 +78,304 @@ async fn net_request<'lua>(lua: &'lua Lua, config: RequestConfig<'lua>) -> LuaRe
 where
     'lua: 'static, // FIXME: Get rid of static lifetime bound here
 {
-    // Create and send the request
-    let client = NetClient::from_registry(lua);
-    let mut request = client.request(config.method, &config.url);
+    let client = create_request_client(lua, &config)?;
+    send_request(lua, &client, config).await
+}
+
+async fn net_download<'lua>(
+    lua: &'lua Lua,
+    (config, path, on_progress): (RequestConfig<'lua>, String, Option<LuaFunction<'lua>>),
+) -> LuaResult<LuaTable<'lua>>
+where
+    'lua: 'static, // FIXME: Get rid of static lifetime bound here
+{
+    let client = create_request_client(lua, &config)?;
+    let decompress = config.options.decompress;
+    let started = Instant::now();
+    let res = send(&client, config).await?;
+    let head = ResponseHead::new(&res, decompress, started);
+    // NOTE: Unsuccessful responses are not written to the file, the
+    // body is returned instead so that the error can be inspected
+    if !res.status().is_success() {
+        let bytes = read_response_body(res, head.format).await?;
+        return head
+            .finished()
+            .into_lua_table(lua, lua.create_string(bytes)?);
+    }
+    // Write the body to the file as it is received, and make sure to
+    // not leave a partially written file behind if anything fails
+    let total = res.content_length();
+    let mut reader = ResponseBodyReader::new(res, head.format)?;
+    let result = async {
+        let mut file = fs::File::create(&path).await.map_err(|e| {
+            LuaError::RuntimeError(format!("Failed to create file at '{path}' - {e}"))
+        })?;
+        while let Some(chunk) = reader.next_chunk().await? {
+            file.write_all(&chunk).await?;
+            if let Some(on_progress) = &on_progress {
+                on_progress.call::<_, ()>((reader.received(), total))?;
+            }
+        }
+        file.flush().await?;
+        Ok::<_, LuaError>(())
+    }
+    .await;
+    if let Err(e) = result {
+        fs::remove_file(&path).await.ok();
+        return Err(e);
+    }
+    head.finished().into_lua_table(lua, "")
+}
+
+/**
+    Gets the client to send a request with, creating a
+    separate client if any of the request options need one.
+*/
+fn create_request_client(lua: &Lua, config: &RequestConfig) -> LuaResult<NetClient> {
+    if config.options.needs_own_client() {
+        NetClientBuilder::new()
+            .headers(&[("User-Agent", create_user_agent_header())])?
+            .connect_timeout(config.options.connect_timeout)
+            .redirects(config.options.redirects)
+            .proxy(config.options.proxy.as_deref())?
+            .build()
+    } else {
+        Ok(NetClient::from_registry(lua))
+    }
+}
+
+fn net_client(lua: &'static Lua, config: ClientConfig) -> LuaResult<LuaTable<'static>> {
+    // NOTE: Default headers given by the user are added after the
+    // user agent header, so that they are able to override it
+    let mut headers = vec![(
+        "User-Agent".to_string(),
+        create_user_agent_header().into_bytes(),
+    )];
+    headers.extend(config.headers);
+    NetClientBuilder::new()
+        .headers(&headers)?
+        .base_url(config.base_url.as_deref())?
+        .cookies(config.cookies)
+        .timeout(config.timeout)
+        .connect_timeout(config.connect_timeout)
+        .redirects(config.redirects)
+        .proxy(config.proxy.as_deref())?
+        .build()?
+        .into_lua_table(lua)
+}
+
+/**
+    Sends a request using the given client, and
+    converts the response into a readonly lua table.
+*/
+async fn send_request<'lua>(
+    lua: &'lua Lua,
+    client: &NetClient,
+    config: RequestConfig<'lua>,
+) -> LuaResult<LuaTable<'lua>>
+where
+    'lua: 'static, // FIXME: Get rid of static lifetime bound here
+{
+    let decompress = config.options.decompress;
+    let stream = config.options.stream;
+    let started = Instant::now();
+    let res = send(client, config).await?;
+    let head = ResponseHead::new(&res, decompress, started);
+    if stream {
+        let reader = ResponseBodyReader::new(res, head.format)?;
+        let body = NetResponseBody::new(reader).into_lua_table(lua)?;
+        head.into_lua_table(lua, body)
+    } else {
+        let bytes = read_response_body(res, head.format).await?;
+        head.finished()
+            .into_lua_table(lua, lua.create_string(bytes)?)
+    }
+}
+
+/**
+    Sends a request using the given client, without reading the response body.
+*/
+async fn send(client: &NetClient, config: RequestConfig<'_>) -> LuaResult<reqwest::Response> {
+    let mut request = client.request(config.method.clone(), &config.url)?;
     for (query, value) in config.query {
         request = request.query(&[(query.to_str()?, value.to_str()?)]);
     }
+    let mut has_content_type = false;
     for (header, value) in config.headers {
-        request = request.header(header.to_str()?, value.to_str()?);
-    }
-    let res = request
-        .body(config.body.unwrap_or_default())
-        .send()
-        .await
-        .into_lua_err()?;
-    // Extract status, headers
-    let res_status = res.status().as_u16();
-    let res_status_text = res.status().canonical_reason();
-    let mut res_headers = res
-        .headers()
-        .iter()
-        .map(|(name, value)| {
-            (
-                name.as_str().to_string(),
-                value.to_str().unwrap().to_owned(),
-            )
-        })
-        .collect::<HashMap<String, String>>();
-    // Read response bytes
-    let mut res_bytes = res.bytes().await.into_lua_err()?.to_vec();
-    // Check for extra options, decompression
-    if config.options.decompress {
+        let header = header.to_str()?;
+        has_content_type |= header.eq_ignore_ascii_case(CONTENT_TYPE.as_str());
+        request = request.header(header, value.to_str()?);
+    }
+    request = match config.auth {
+        Some(RequestAuth::Basic { username, password }) => request.basic_auth(username, password),
+        Some(RequestAuth::Bearer(token)) => request.bearer_auth(token),
+        None => request,
+    };
+    if let Some(timeout) = config.options.timeout {
+        request = request.timeout(timeout);
+    }
+    let (request, feed_body) = set_request_body(request, config.body, has_content_type).await?;
+    // Send the request, retrying idempotent requests if they fail
+    let retries = if config.method.is_idempotent() {
+        config.options.retries
+    } else {
+        0
+    };
+    let (res, fed) = tokio::join!(
+        send_with_retries(request, retries, config.options.retry_delay),
+        feed_body
+    );
+    // NOTE: Errors from a chunk iterator also make sending the request fail,
+    // so we return those first since they are more useful to the user
+    fed?;
+    res
+}
+
+/**
+    The status and headers of a response, as well as the format
+    that the body of the response should be decompressed with.
+
+    Also keeps track of when the request was started, so that the time
+    taken to receive the response head and full body can be given to lua.
+*/
+struct ResponseHead {
+    url: String,
+    version: Version,
+    status: StatusCode,
+    headers: HashMap<String, String>,
+    format: Option<CompressDecompressFormat>,
+    started: Instant,
+    head_elapsed: time::Duration,
+    total_elapsed: Option<time::Duration>,
+}
+
+impl ResponseHead {
+    fn new(res: &reqwest::Response, decompress: bool, started: Instant) -> Self {
+        let mut headers = res
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.as_str().to_string(),
+                    value.to_str().unwrap().to_owned(),
+                )
+            })
+            .collect::<HashMap<String, String>>();
         // NOTE: Header names are guaranteed to be lowercase because of the above
         // transformations of them into the hashmap, so we can compare directly
-        let format = res_headers.iter().find_map(|(name, val)| {
-            if name == CONTENT_ENCODING.as_str() {
-                CompressDecompressFormat::detect_from_header_str(val)
-            } else {
-                None
-            }
-        });
-        if let Some(format) = format {
-            res_bytes = decompress(format, res_bytes).await?;
-            let content_encoding_header_str = CONTENT_ENCODING.as_str();
-            let content_length_header_str = CONTENT_LENGTH.as_str();
-            res_headers.retain(|name, _| {
-                name != content_encoding_header_str && name != content_length_header_str
-            });
+        let format = if decompress {
+            headers
+                .get(CONTENT_ENCODING.as_str())
+                .and_then(CompressDecompressFormat::detect_from_header_str)
+        } else {
+            None
+        };
+        if format.is_some() {
+            headers.remove(CONTENT_ENCODING.as_str());
+            headers.remove(CONTENT_LENGTH.as_str());
+        }
+        Self {
+            url: res.url().to_string(),
+            version: res.version(),
+            status: res.status(),
+            headers,
+            format,
+            started,
+            head_elapsed: started.elapsed(),
+            total_elapsed: None,
         }
     }
-    // Construct and return a readonly lua table with results
-    TableBuilder::new(lua)?
-        .with_value("ok", (200..300).contains(&res_status))?
-        .with_value("statusCode", res_status)?
-        .with_value("statusMessage", res_status_text)?
-        .with_value("headers", res_headers)?
-        .with_value("body", lua.create_string(&res_bytes)?)?
-        .build_readonly()
+
+    /**
+        Marks the full body of the response as received.
+
+        Responses with streamed bodies are never marked as received,
+        since the body is read by the user after the response is returned.
+    */
+    fn finished(mut self) -> Self {
+        self.total_elapsed = Some(self.started.elapsed());
+        self
+    }
+
+    fn into_lua_table<'lua>(
+        self,
+        lua: &'lua Lua,
+        body: impl IntoLua<'lua>,
+    ) -> LuaResult<LuaTable<'lua>> {
+        let timing = TableBuilder::new(lua)?
+            .with_value("head", self.head_elapsed.as_secs_f64())?
+            .with_value("total", self.total_elapsed.map(|d| d.as_secs_f64()))?
+            .build_readonly()?;
+        TableBuilder::new(lua)?
+            .with_value("ok", self.status.is_success())?
+            .with_value("url", self.url)?
+            .with_value("httpVersion", http_version_str(self.version))?
+            .with_value("statusCode", self.status.as_u16())?
+            .with_value("statusMessage", self.status.canonical_reason())?
+            .with_value("headers", self.headers)?
+            .with_value("body", body)?
+            .with_value("timing", timing)?
+            .build_readonly()
+    }
+}
+
+fn http_version_str(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "HTTP/1.1",
+    }
+}
+
+async fn read_response_body(
+    res: reqwest::Response,
+    format: Option<CompressDecompressFormat>,
+) -> LuaResult<Vec<u8>> {
+    let bytes = res.bytes().await.into_lua_err()?.to_vec();
+    match format {
+        Some(format) => decompress(format, bytes).await,
+        None => Ok(bytes),
+    }
+}
+
+async fn send_with_retries(
+    request: RequestBuilder,
+    retries: u32,
+    retry_delay: time::Duration,
+) -> LuaResult<reqwest::Response> {
+    let mut attempt = 0;
+    loop {
+        let result = match request.try_clone() {
+            Some(request) => request.send().await,
+            // NOTE: Streamed bodies can not be cloned, and are never retried
+            None => return request.send().await.into_lua_err(),
+        };
+        let should_retry = match &result {
+            Ok(res) => is_retryable_status(res.status()),
+            Err(e) => e.is_connect() || e.is_timeout(),
+        };
+        if !should_retry || attempt >= retries {
+            return result.into_lua_err();
+        }
+        // NOTE: The delay doubles for each retry, to give servers time to recover
+        let delay = retry_delay.saturating_mul(2u32.saturating_pow(attempt));
+        time::sleep(delay.min(MAX_RETRY_DELAY)).await;
+        attempt += 1;
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
 }
 
 async fn net_socket<'lua>(lua: &'lua Lua, url: String) -> LuaResult<LuaTable>
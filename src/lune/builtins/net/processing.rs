@@ -6,6 +6,8 @@ use mlua::prelude::*;
 
 use crate::lune::util::TableBuilder;
 
+use super::config::normalize_method;
+
 static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
@@ -39,7 +41,7 @@ impl ProcessedRequest {
             Ok(b) => b.to_vec(),
         };
 
-        let method = head.method.to_string().to_ascii_uppercase();
+        let method = normalize_method(head.method.as_str());
 
         let mut path = head.uri.path().to_string();
         if path.is_empty() {
@@ -8,7 +8,7 @@ use hyper::{
 
 use hyper_tungstenite::{is_upgrade_request, upgrade, HyperWebsocket};
 use mlua::prelude::*;
-use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
 
 use crate::lune::{
     scheduler::Scheduler,
@@ -63,6 +63,13 @@ where
     let response_senders_bg = Arc::clone(&response_senders);
     let response_senders_lua = Arc::clone(&response_senders_bg);
 
+    // Keep track of the amount of requests currently being handled, so that
+    // stopping the server can wait for those requests to get their responses
+    let (active_requests_tx, mut active_requests_rx) = watch::channel(0usize);
+    let active_requests_bg = Arc::new(active_requests_tx);
+    let (stopping_tx, stopping_rx) = oneshot::channel::<()>();
+    let (stopped_tx, mut stopped_rx) = oneshot::channel::<()>();
+
     // Create our background service which will accept
     // requests, do some processing, then forward to lua
     let has_websocket_handler = config.handle_web_socket.is_some();
@@ -70,11 +77,13 @@ where
         let tx_request = Arc::clone(&tx_request_arc);
         let tx_websocket = Arc::clone(&tx_websocket_arc);
         let response_senders = Arc::clone(&response_senders_bg);
+        let active_requests = Arc::clone(&active_requests_bg);
 
         let handler = service_fn(move |mut req| {
             let tx_request = Arc::clone(&tx_request);
             let tx_websocket = Arc::clone(&tx_websocket);
             let response_senders = Arc::clone(&response_senders);
+            let active_requests = Arc::clone(&active_requests);
             async move {
                 // FUTURE: Improve error messages when lua is busy and queue is full
                 if has_websocket_handler && is_upgrade_request(&req) {
@@ -87,16 +96,20 @@ where
                     }
                     Ok(response)
                 } else {
+                    let _active = ActiveRequestGuard::new(active_requests);
                     let processed = ProcessedRequest::from_request(req).await?;
                     let request_id = processed.id;
-                    if (tx_request.send(processed).await).is_err() {
-                        return Err(LuaError::runtime("Lua handler is busy"));
-                    }
+                    // NOTE: The response sender must be inserted before the request is sent
+                    // to lua, since lua may handle the request and try to respond right away
                     let (response_tx, response_rx) = oneshot::channel::<NetServeResponse>();
                     response_senders
                         .lock()
                         .await
                         .insert(request_id, response_tx);
+                    if (tx_request.send(processed).await).is_err() {
+                        response_senders.lock().await.remove(&request_id);
+                        return Err(LuaError::runtime("Lua handler is busy"));
+                    }
                     match response_rx.await {
                         Err(_) => Err(LuaError::runtime("Internal Server Error")),
                         Ok(r) => r.into_response(),
@@ -110,7 +123,7 @@ where
 
     // Start up our service
     sched.spawn(async move {
-        let result = builder
+        let server = builder
             .http1_only(true) // Web sockets can only use http1
             .http1_keepalive(true) // Web sockets must be kept alive
             .serve(hyper_make_service)
@@ -120,10 +133,25 @@ where
                     // was garbage collected by lua without being used
                     yield_forever().await;
                 }
+                stopping_tx.send(()).ok();
             });
-        if let Err(e) = result.await {
-            eprintln!("Net serve error: {e}")
+        // NOTE: Graceful shutdown waits for all connections to close, but http
+        // clients may keep idle connections that never send a request open
+        // in their connection pools, so once the server is stopping we only
+        // wait for requests that are currently being handled to finish
+        let requests_finished = async move {
+            stopping_rx.await.ok();
+            active_requests_rx.wait_for(|count| *count == 0).await.ok();
+        };
+        tokio::select! {
+            result = server => {
+                if let Err(e) = result {
+                    eprintln!("Net serve error: {e}")
+                }
+            }
+            _ = requests_finished => {}
         }
+        stopped_tx.send(()).ok();
     });
 
     // Spawn a local thread with access to lua and the same lifetime
@@ -131,10 +159,11 @@ where
         loop {
             // Wait for either a request or a websocket to handle,
             // if we got neither it means both channels were dropped
-            // and our server has stopped, either gracefully or panic
+            // or our server has stopped, either gracefully or panic
             let (req, sock) = tokio::select! {
                 req = rx_request.recv() => (req, None),
                 sock = rx_websocket.recv() => (None, sock),
+                _ = &mut stopped_rx => (None, None),
             };
             if req.is_none() && sock.is_none() {
                 break;
@@ -222,3 +251,22 @@ where
         .with_function("stop", handle_stop)?
         .build_readonly()
 }
+
+/**
+    Guard that counts a request as being handled for as long as it
+    is alive, even if the request handler future is dropped early.
+*/
+struct ActiveRequestGuard(Arc<watch::Sender<usize>>);
+
+impl ActiveRequestGuard {
+    fn new(active_requests: Arc<watch::Sender<usize>>) -> Self {
+        active_requests.send_modify(|count| *count += 1);
+        Self(active_requests)
+    }
+}
+
+impl Drop for ActiveRequestGuard {
+    fn drop(&mut self) {
+        self.0.send_modify(|count| *count -= 1);
+    }
+}
@@ -0,0 +1,131 @@
+use std::{collections::HashSet, fmt};
+
+use mlua::prelude::*;
+
+use crate::lune::util::TableBuilder;
+
+use super::path::{
+    find_link_target_symlink, find_parent_symlink, is_safe_path, normalize_path,
+    resolve_link_target,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveEntryKind {
+    File,
+    Dir,
+    Symlink,
+    HardLink,
+}
+
+impl fmt::Display for ArchiveEntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::File => "file",
+                Self::Dir => "dir",
+                Self::Symlink => "symlink",
+                Self::HardLink => "hardlink",
+            }
+        )
+    }
+}
+
+/**
+    An entry in an archive, without its contents.
+
+    The path of the entry always uses forward slashes as separators,
+    and never contains any leading `./` or trailing slashes.
+*/
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub kind: ArchiveEntryKind,
+    pub size: u64,
+    pub modified_at: Option<f64>,
+    pub mode: Option<u32>,
+    pub link_target: Option<String>,
+}
+
+impl ArchiveEntry {
+    /**
+        Checks that the entry can be extracted without writing
+        or linking to anything outside of the target directory.
+    */
+    pub fn check_safe(&self) -> LuaResult<()> {
+        if !is_safe_path(&self.path) {
+            return Err(LuaError::RuntimeError(format!(
+                "Archive entry '{}' has an unsafe path - \
+                entry paths must be relative and may not contain '..'",
+                self.path
+            )));
+        }
+        let link_is_safe = match (self.kind, &self.link_target) {
+            (ArchiveEntryKind::Symlink, Some(target)) => {
+                resolve_link_target(&self.path, target).is_some()
+            }
+            (ArchiveEntryKind::HardLink, Some(target)) => is_safe_path(target),
+            (ArchiveEntryKind::Symlink | ArchiveEntryKind::HardLink, None) => false,
+            _ => true,
+        };
+        if !link_is_safe {
+            return Err(LuaError::RuntimeError(format!(
+                "Archive entry '{}' links to '{}', which is outside of the archive",
+                self.path,
+                self.link_target.as_deref().unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+
+    /**
+        Checks that the entry, and anything it links to, can be reached
+        without passing through any of the symlinks in the same archive.
+
+        Once extracted, symlinks are followed by the filesystem instead of
+        being resolved as text, so passing through them could end up
+        outside of the target directory even when every path looks safe.
+    */
+    pub fn check_symlinks(&self, symlinks: &HashSet<String>) -> LuaResult<()> {
+        if let Some(symlink) = find_parent_symlink(&self.path, symlinks) {
+            return Err(LuaError::RuntimeError(format!(
+                "Archive entry '{}' is inside of the symlink '{symlink}' \
+                from the same archive, which is not allowed",
+                self.path
+            )));
+        }
+        let link_symlink = match (self.kind, &self.link_target) {
+            (ArchiveEntryKind::Symlink, Some(target)) => {
+                find_link_target_symlink(&self.path, target, symlinks)
+            }
+            (ArchiveEntryKind::HardLink, Some(target)) => {
+                find_parent_symlink(&normalize_path(target), symlinks)
+            }
+            _ => None,
+        };
+        if let Some(symlink) = link_symlink {
+            return Err(LuaError::RuntimeError(format!(
+                "Archive entry '{}' links to '{}' through the symlink '{symlink}' \
+                from the same archive, which is not allowed",
+                self.path,
+                self.link_target.as_deref().unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<'lua> IntoLua<'lua> for ArchiveEntry {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        TableBuilder::new(lua)?
+            .with_value("path", self.path)?
+            .with_value("kind", self.kind.to_string())?
+            .with_value("size", self.size)?
+            .with_value("modifiedAt", self.modified_at)?
+            .with_value("mode", self.mode)?
+            .with_value("linkTarget", self.link_target)?
+            .build_readonly()
+            .map(LuaValue::Table)
+    }
+}
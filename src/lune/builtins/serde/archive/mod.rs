@@ -0,0 +1,288 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use mlua::prelude::*;
+use tokio::task;
+
+use crate::lune::util::TableBuilder;
+
+use super::compress_decompress::{
+    compress, decompress, CompressDecompressFormat, CompressDecompressOptions,
+};
+
+mod entry;
+mod path;
+mod source;
+mod tar;
+mod zip;
+
+use entry::{ArchiveEntry, ArchiveEntryKind};
+use path::normalize_path;
+use source::collect_source_entries;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable<'static>> {
+    TableBuilder::new(lua)?
+        .with_async_function("list", archive_list)?
+        .with_async_function("read", archive_read)?
+        .with_async_function("extract", archive_extract)?
+        .with_async_function("create", archive_create)?
+        .build_readonly()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar(Option<CompressDecompressFormat>),
+}
+
+impl ArchiveFormat {
+    /**
+        Decompresses the given archive, if it is a compressed tar archive.
+    */
+    async fn decompress(&self, bytes: impl AsRef<[u8]>) -> LuaResult<Vec<u8>> {
+        match self {
+            Self::Tar(Some(compression)) => decompress(*compression, bytes).await,
+            _ => Ok(bytes.as_ref().to_vec()),
+        }
+    }
+
+    /**
+        Compresses the given archive, if it is a compressed tar archive.
+    */
+    async fn compress(&self, bytes: Vec<u8>) -> LuaResult<Vec<u8>> {
+        match self {
+            Self::Tar(Some(compression)) => {
+                compress(*compression, bytes, CompressDecompressOptions::default()).await
+            }
+            _ => Ok(bytes),
+        }
+    }
+
+    fn visit_entries(
+        &self,
+        bytes: &[u8],
+        visit: impl FnMut(ArchiveEntry, &mut dyn Read) -> LuaResult<()>,
+    ) -> LuaResult<()> {
+        match self {
+            Self::Zip => zip::visit_entries(bytes, visit),
+            Self::Tar(_) => tar::visit_entries(bytes, visit),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for ArchiveFormat {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            let kind = s.to_string_lossy().to_ascii_lowercase();
+            let compression = match kind.trim() {
+                "zip" => return Ok(Self::Zip),
+                "tar" => return Ok(Self::Tar(None)),
+                "tgz" => Some(CompressDecompressFormat::GZip),
+                kind => match kind.strip_prefix("tar.") {
+                    Some("br" | "brotli") => Some(CompressDecompressFormat::Brotli),
+                    Some("bz2" | "bzip2") => Some(CompressDecompressFormat::BZip2),
+                    Some("gz" | "gzip") => Some(CompressDecompressFormat::GZip),
+                    Some("lz4") => Some(CompressDecompressFormat::LZ4),
+                    Some("xz") => Some(CompressDecompressFormat::XZ),
+                    Some("zlib") => Some(CompressDecompressFormat::ZLib),
+                    Some("zst" | "zstd") => Some(CompressDecompressFormat::Zstd),
+                    _ => None,
+                },
+            };
+            match compression {
+                Some(compression) => Ok(Self::Tar(Some(compression))),
+                None => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "ArchiveFormat",
+                    message: Some(format!(
+                        "Invalid format '{kind}', valid formats are:  \
+                        zip, tar, tar.br, tar.bz2, tar.gz, tar.lz4, tar.xz, tar.zlib, tar.zst"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ArchiveFormat",
+                message: None,
+            })
+        }
+    }
+}
+
+async fn archive_list<'lua>(
+    _: &'lua Lua,
+    (format, archive): (ArchiveFormat, LuaString<'lua>),
+) -> LuaResult<Vec<ArchiveEntry>> {
+    let bytes = format.decompress(archive).await?;
+    task::spawn_blocking(move || {
+        let mut entries = Vec::new();
+        format.visit_entries(&bytes, |entry, _| {
+            entries.push(entry);
+            Ok(())
+        })?;
+        Ok(entries)
+    })
+    .await
+    .into_lua_err()?
+}
+
+async fn archive_read<'lua>(
+    lua: &'lua Lua,
+    (format, archive, path): (ArchiveFormat, LuaString<'lua>, String),
+) -> LuaResult<Option<LuaString<'lua>>> {
+    let bytes = format.decompress(archive).await?;
+    let path = normalize_path(&path);
+    let contents = task::spawn_blocking(move || {
+        let mut contents = None;
+        format.visit_entries(&bytes, |entry, reader| {
+            if entry.kind == ArchiveEntryKind::File && entry.path == path {
+                // NOTE: Entry sizes come from the archive itself and may be
+                // anything, so they must not be trusted for allocating, and
+                // the buffer may never need to be larger than the archive
+                let capacity = entry.size.min(bytes.len() as u64) as usize;
+                let mut buffer = Vec::with_capacity(capacity);
+                reader.read_to_end(&mut buffer)?;
+                check_entry_size(&entry, buffer.len() as u64)?;
+                contents = Some(buffer);
+            }
+            Ok(())
+        })?;
+        Ok::<_, LuaError>(contents)
+    })
+    .await
+    .into_lua_err()??;
+    contents
+        .map(|contents| lua.create_string(contents))
+        .transpose()
+}
+
+async fn archive_extract<'lua>(
+    _: &'lua Lua,
+    (format, archive, dir): (ArchiveFormat, LuaString<'lua>, String),
+) -> LuaResult<()> {
+    let bytes = format.decompress(archive).await?;
+    task::spawn_blocking(move || {
+        // NOTE: All entries are checked before anything gets written, so that
+        // archives with unsafe entries are rejected without being partially extracted
+        let mut symlinks = HashSet::new();
+        format.visit_entries(&bytes, |entry, _| {
+            entry.check_safe()?;
+            if entry.kind == ArchiveEntryKind::Symlink {
+                symlinks.insert(entry.path);
+            }
+            Ok(())
+        })?;
+        format.visit_entries(&bytes, |entry, _| entry.check_symlinks(&symlinks))?;
+        let dir = PathBuf::from(dir);
+        fs::create_dir_all(&dir)?;
+        let dir = dir.canonicalize()?;
+        format.visit_entries(&bytes, |entry, reader| extract_entry(&dir, &entry, reader))
+    })
+    .await
+    .into_lua_err()?
+}
+
+async fn archive_create<'lua>(
+    lua: &'lua Lua,
+    (format, dir): (ArchiveFormat, String),
+) -> LuaResult<LuaString<'lua>> {
+    let dir = PathBuf::from(dir);
+    if !dir.is_dir() {
+        return Err(LuaError::RuntimeError(format!(
+            "No directory exists at the path '{}'",
+            dir.display()
+        )));
+    }
+    let bytes = task::spawn_blocking(move || {
+        let entries = collect_source_entries(&dir)?;
+        match format {
+            ArchiveFormat::Zip => zip::create(entries),
+            ArchiveFormat::Tar(_) => tar::create(entries),
+        }
+    })
+    .await
+    .into_lua_err()??;
+    let bytes = format.compress(bytes).await?;
+    lua.create_string(bytes)
+}
+
+fn check_entry_size(entry: &ArchiveEntry, read: u64) -> LuaResult<()> {
+    if read < entry.size {
+        return Err(LuaError::RuntimeError(format!(
+            "Archive entry '{}' is truncated, expected {} bytes but got {read}",
+            entry.path, entry.size
+        )));
+    }
+    Ok(())
+}
+
+/**
+    Checks that the given path is inside of the given canonical directory, once any
+    symlinks that already exist on disk have been followed, so that nothing can be
+    written outside of it through symlinks that were extracted or existed before.
+*/
+fn check_inside_dir(dir: &Path, entry: &ArchiveEntry, path: &Path) -> LuaResult<()> {
+    let mut existing = path;
+    while !existing.exists() {
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => break,
+        }
+    }
+    if existing.canonicalize()?.starts_with(dir) {
+        Ok(())
+    } else {
+        Err(LuaError::RuntimeError(format!(
+            "Archive entry '{}' would be written outside of the target directory",
+            entry.path
+        )))
+    }
+}
+
+fn extract_entry(dir: &Path, entry: &ArchiveEntry, reader: &mut dyn Read) -> LuaResult<()> {
+    let path = dir.join(&entry.path);
+    if let Some(parent) = path.parent() {
+        check_inside_dir(dir, entry, parent)?;
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // NOTE: Existing symlinks are replaced instead of being followed,
+    // since following them could write outside of the target directory
+    if entry.kind != ArchiveEntryKind::Dir && path.is_symlink() {
+        fs::remove_file(&path)?;
+    }
+    match entry.kind {
+        ArchiveEntryKind::Dir => fs::create_dir_all(&path)?,
+        ArchiveEntryKind::File => {
+            let written = io::copy(reader, &mut File::create(&path)?)?;
+            check_entry_size(entry, written)?;
+            #[cfg(unix)]
+            if let Some(mode) = entry.mode {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+            }
+        }
+        ArchiveEntryKind::HardLink => {
+            let target = dir.join(entry.link_target.as_deref().unwrap_or_default());
+            check_inside_dir(dir, entry, &target)?;
+            fs::copy(target, &path)?;
+        }
+        ArchiveEntryKind::Symlink => {
+            let target = entry.link_target.as_deref().unwrap_or_default();
+            if path.is_file() {
+                fs::remove_file(&path)?;
+            }
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(target, &path)?;
+            #[cfg(windows)]
+            std::os::windows::fs::symlink_file(target, &path)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+
+/**
+    Normalizes the path of an archive entry so that it uses forward slashes,
+    and does not contain any empty or `.` components, or trailing slashes.
+
+    Leading slashes and `..` components are kept as they are, so
+    that unsafe paths can still be listed, and then be rejected.
+*/
+pub fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let components = path
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect::<Vec<_>>()
+        .join("/");
+    if path.starts_with('/') {
+        format!("/{components}")
+    } else {
+        components
+    }
+}
+
+/**
+    Checks if a normalized path is relative and does not contain
+    any `..` components, meaning that it can not refer to anything
+    outside of the directory that an archive is being extracted into.
+*/
+pub fn is_safe_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.starts_with('/')
+        && !has_drive_prefix(path)
+        && path.split('/').all(|component| component != "..")
+}
+
+/**
+    Resolves the target of a symlink relative to the directory containing it,
+    returning the path that it points to relative to the root of the archive.
+
+    Returns `None` if the target is absolute, or if it points outside of the archive.
+*/
+pub fn resolve_link_target(link_path: &str, target: &str) -> Option<String> {
+    let target = target.replace('\\', "/");
+    if target.starts_with('/') || has_drive_prefix(&target) {
+        return None;
+    }
+    let mut resolved = link_path.split('/').collect::<Vec<_>>();
+    resolved.pop();
+    for component in target.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                resolved.pop()?;
+            }
+            component => resolved.push(component),
+        }
+    }
+    Some(resolved.join("/"))
+}
+
+/**
+    Finds the first symlink that a normalized path, relative to the root of
+    the archive, would pass through as a directory, if there is one.
+*/
+pub fn find_parent_symlink<'a>(path: &str, symlinks: &'a HashSet<String>) -> Option<&'a str> {
+    let mut components = path.split('/').collect::<Vec<_>>();
+    components.pop();
+    let mut parent = String::new();
+    for component in components {
+        if !parent.is_empty() {
+            parent.push('/');
+        }
+        parent.push_str(component);
+        if let Some(symlink) = symlinks.get(&parent) {
+            return Some(symlink);
+        }
+    }
+    None
+}
+
+/**
+    Finds the first symlink that resolving the target of a symlink would pass
+    through as a directory, if there is one, the same way as [`resolve_link_target`].
+
+    Symlinks are resolved by the filesystem and not as text, so a target such as
+    `other/..` only stays inside of the archive if `other` is not also a symlink.
+*/
+pub fn find_link_target_symlink<'a>(
+    link_path: &str,
+    target: &str,
+    symlinks: &'a HashSet<String>,
+) -> Option<&'a str> {
+    let target = target.replace('\\', "/");
+    let mut resolved = link_path.split('/').collect::<Vec<_>>();
+    resolved.pop();
+    for component in target.split('/') {
+        if let Some(symlink) = symlinks.get(&resolved.join("/")) {
+            return Some(symlink);
+        }
+        match component {
+            "" | "." => {}
+            ".." => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    None
+}
+
+fn has_drive_prefix(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
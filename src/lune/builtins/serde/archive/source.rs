@@ -0,0 +1,64 @@
+use std::{
+    fs::{self, Metadata},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/**
+    A file, directory, or symlink that should be added to an archive.
+*/
+pub struct SourceEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub metadata: Metadata,
+}
+
+impl SourceEntry {
+    pub fn modified_at(&self) -> Option<f64> {
+        let modified = self.metadata.modified().ok()?;
+        let duration = modified.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+        Some(duration.as_secs_f64())
+    }
+}
+
+/**
+    Recursively collects all of the entries in a directory,
+    sorted by name so that created archives are deterministic.
+
+    Symlinks are not followed, and are added as symlinks instead.
+*/
+pub fn collect_source_entries(dir: &Path) -> io::Result<Vec<SourceEntry>> {
+    let mut entries = Vec::new();
+    collect_into(dir, "", &mut entries)?;
+    Ok(entries)
+}
+
+fn collect_into(dir: &Path, prefix: &str, entries: &mut Vec<SourceEntry>) -> io::Result<()> {
+    let mut children = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    children.sort_by_key(|child| child.file_name());
+    for child in children {
+        let file_name = child.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "File path could not be converted into a string: '{}'",
+                    child.path().display()
+                ),
+            ));
+        };
+        let name = format!("{prefix}{file_name}");
+        let metadata = fs::symlink_metadata(child.path())?;
+        let is_dir = metadata.is_dir();
+        entries.push(SourceEntry {
+            name: name.clone(),
+            path: child.path(),
+            metadata,
+        });
+        if is_dir {
+            collect_into(&child.path(), &format!("{name}/"), entries)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::io::Read;
+
+use ::tar::{Archive, Builder};
+use mlua::prelude::*;
+
+use super::{
+    entry::{ArchiveEntry, ArchiveEntryKind},
+    path::normalize_path,
+    source::SourceEntry,
+};
+
+/**
+    Calls the given function for each entry in a tar archive,
+    along with a reader for the contents of the entry.
+
+    Entries that are not files, directories, or links, such
+    as devices and fifos, are skipped since they can not be
+    represented in a portable way.
+*/
+pub fn visit_entries(
+    bytes: &[u8],
+    mut visit: impl FnMut(ArchiveEntry, &mut dyn Read) -> LuaResult<()>,
+) -> LuaResult<()> {
+    let mut archive = Archive::new(bytes);
+    for entry in archive.entries().map_err(tar_error)? {
+        let mut entry = entry.map_err(tar_error)?;
+        let header = entry.header();
+        let entry_type = header.entry_type();
+        let kind = if entry_type.is_file() {
+            ArchiveEntryKind::File
+        } else if entry_type.is_dir() {
+            ArchiveEntryKind::Dir
+        } else if entry_type.is_symlink() {
+            ArchiveEntryKind::Symlink
+        } else if entry_type.is_hard_link() {
+            ArchiveEntryKind::HardLink
+        } else {
+            continue;
+        };
+        let path = normalize_path(&String::from_utf8_lossy(&entry.path_bytes()));
+        if path.is_empty() {
+            continue;
+        }
+        let info = ArchiveEntry {
+            path,
+            kind,
+            size: entry.size(),
+            modified_at: header.mtime().ok().map(|mtime| mtime as f64),
+            mode: header.mode().ok().map(|mode| mode & 0o777),
+            // NOTE: Hard links point to other entries in the archive, while
+            // symlinks point to paths relative to the directory they are in
+            link_target: entry.link_name_bytes().map(|target| {
+                let target = String::from_utf8_lossy(&target);
+                if kind == ArchiveEntryKind::HardLink {
+                    normalize_path(&target)
+                } else {
+                    target.into_owned()
+                }
+            }),
+        };
+        visit(info, &mut entry)?;
+    }
+    Ok(())
+}
+
+/**
+    Creates a tar archive containing the given entries.
+*/
+pub fn create(entries: Vec<SourceEntry>) -> LuaResult<Vec<u8>> {
+    let mut builder = Builder::new(Vec::new());
+    builder.follow_symlinks(false);
+    for entry in entries {
+        builder.append_path_with_name(&entry.path, &entry.name)?;
+    }
+    builder.into_inner().map_err(LuaError::external)
+}
+
+fn tar_error(e: impl ToString) -> LuaError {
+    LuaError::RuntimeError(format!("Invalid tar archive - {}", e.to_string()))
+}
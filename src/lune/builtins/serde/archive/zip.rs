@@ -0,0 +1,122 @@
+use std::{
+    fs::File,
+    io::{self, Cursor, Read},
+};
+
+use ::zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter};
+use chrono::{DateTime as ChronoDateTime, Datelike, NaiveDate, Timelike, Utc};
+use mlua::prelude::*;
+
+use super::{
+    entry::{ArchiveEntry, ArchiveEntryKind},
+    path::normalize_path,
+    source::SourceEntry,
+};
+
+/**
+    Calls the given function for each entry in a zip archive,
+    along with a reader for the contents of the entry.
+*/
+pub fn visit_entries(
+    bytes: &[u8],
+    mut visit: impl FnMut(ArchiveEntry, &mut dyn Read) -> LuaResult<()>,
+) -> LuaResult<()> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(zip_error)?;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(zip_error)?;
+        let path = normalize_path(file.name());
+        if path.is_empty() {
+            continue;
+        }
+        let kind = if file.is_dir() {
+            ArchiveEntryKind::Dir
+        } else if file.is_symlink() {
+            ArchiveEntryKind::Symlink
+        } else {
+            ArchiveEntryKind::File
+        };
+        // NOTE: The target of a symlink in a zip archive is stored as its contents
+        let link_target = if kind == ArchiveEntryKind::Symlink {
+            let mut target = String::new();
+            file.read_to_string(&mut target).map_err(zip_error)?;
+            Some(target)
+        } else {
+            None
+        };
+        let entry = ArchiveEntry {
+            path,
+            kind,
+            size: file.size(),
+            modified_at: file.last_modified().and_then(zip_time_to_timestamp),
+            mode: file.unix_mode().map(|mode| mode & 0o777),
+            link_target,
+        };
+        visit(entry, &mut file)?;
+    }
+    Ok(())
+}
+
+/**
+    Creates a zip archive containing the given entries.
+*/
+pub fn create(entries: Vec<SourceEntry>) -> LuaResult<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for entry in entries {
+        let mut options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(entry.metadata.len() >= u32::MAX as u64);
+        if let Some(time) = entry.modified_at().and_then(timestamp_to_zip_time) {
+            options = options.last_modified_time(time);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            options = options.unix_permissions(entry.metadata.permissions().mode() & 0o777);
+        }
+        if entry.metadata.is_dir() {
+            writer
+                .add_directory(&entry.name, options)
+                .map_err(zip_error)?;
+        } else if entry.metadata.is_symlink() {
+            let target = std::fs::read_link(&entry.path)?;
+            let target = target.to_string_lossy().replace('\\', "/");
+            writer
+                .add_symlink(&entry.name, target, options)
+                .map_err(zip_error)?;
+        } else {
+            writer.start_file(&entry.name, options).map_err(zip_error)?;
+            io::copy(&mut File::open(&entry.path)?, &mut writer)?;
+        }
+    }
+    Ok(writer.finish().map_err(zip_error)?.into_inner())
+}
+
+fn zip_error(e: impl ToString) -> LuaError {
+    LuaError::RuntimeError(format!("Invalid zip archive - {}", e.to_string()))
+}
+
+// NOTE: Zip archives store times without a timezone, so they
+// are always read and written as utc times to be consistent
+
+fn zip_time_to_timestamp(time: DateTime) -> Option<f64> {
+    let time = NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?
+        .and_hms_opt(
+            time.hour() as u32,
+            time.minute() as u32,
+            time.second() as u32,
+        )?;
+    Some(time.and_utc().timestamp() as f64)
+}
+
+fn timestamp_to_zip_time(timestamp: f64) -> Option<DateTime> {
+    let time = ChronoDateTime::<Utc>::from_timestamp(timestamp as i64, 0)?;
+    DateTime::from_date_and_time(
+        time.year().try_into().ok()?,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .ok()
+}
@@ -0,0 +1,370 @@
+use lz4_flex::{compress_prepend_size, decompress_size_prepended};
+use mlua::prelude::*;
+use tokio::{
+    io::{copy, BufReader},
+    task,
+};
+
+use async_compression::{
+    brotli::EncoderParams as BrotliParams,
+    tokio::bufread::{
+        BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, ZlibDecoder, ZlibEncoder,
+    },
+    Level,
+};
+
+mod stream;
+
+pub use stream::CompressDecompressStream;
+
+#[derive(Debug, Clone, Copy)]
+pub enum CompressDecompressFormat {
+    Brotli,
+    BZip2,
+    GZip,
+    LZ4,
+    XZ,
+    ZLib,
+    Zstd,
+}
+
+impl CompressDecompressFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Brotli => "brotli",
+            Self::BZip2 => "bzip2",
+            Self::GZip => "gzip",
+            Self::LZ4 => "lz4",
+            Self::XZ => "xz",
+            Self::ZLib => "zlib",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /**
+        If the format is compressed and decompressed synchronously on a
+        blocking thread, instead of using an asynchronous encoder or decoder.
+    */
+    pub fn is_blocking(&self) -> bool {
+        matches!(self, Self::LZ4 | Self::BZip2 | Self::XZ | Self::Zstd)
+    }
+
+    /**
+        Detects the format of some compressed data from its magic bytes.
+
+        Note that the lz4 format used by `serde.compress` has no magic bytes, only
+        lz4 frames are detected, and brotli data can only be detected if it starts
+        with one of the metadata blocks that some brotli encoders write.
+    */
+    pub fn detect_from_bytes(bytes: impl AsRef<[u8]>) -> Option<Self> {
+        match bytes.as_ref() {
+            // https://github.com/PSeitz/lz4_flex/blob/main/src/frame/header.rs#L28
+            b if b.len() >= 4
+                && matches!(
+                    u32::from_le_bytes(b[0..4].try_into().unwrap()),
+                    0x184D2204 | 0x184C2102
+                ) =>
+            {
+                Some(Self::LZ4)
+            }
+            // https://github.com/dropbox/rust-brotli/blob/master/src/enc/brotli_bit_stream.rs#L2805
+            b if b.len() >= 4
+                && matches!(
+                    b[0..3],
+                    [0xE1, 0x97, 0x81] | [0xE1, 0x97, 0x82] | [0xE1, 0x97, 0x80]
+                ) =>
+            {
+                Some(Self::Brotli)
+            }
+            // https://datatracker.ietf.org/doc/html/rfc8878#section-3.1.1
+            b if b.len() >= 4 && matches!(b[0..4], [0x28, 0xB5, 0x2F, 0xFD]) => Some(Self::Zstd),
+            // https://tukaani.org/xz/xz-file-format.txt section 2.1.1.1
+            b if b.len() >= 6 && matches!(b[0..6], [0xFD, b'7', b'z', b'X', b'Z', 0x00]) => {
+                Some(Self::XZ)
+            }
+            // https://github.com/dsnet/compress/blob/master/doc/bzip2-format.pdf
+            b if b.len() >= 4 && matches!(b[0..4], [b'B', b'Z', b'h', b'1'..=b'9']) => {
+                Some(Self::BZip2)
+            }
+            // https://github.com/rust-lang/flate2-rs/blob/main/src/gz/mod.rs#L135
+            b if b.len() >= 3 && matches!(b[0..3], [0x1F, 0x8B, 0x08]) => Some(Self::GZip),
+            // https://stackoverflow.com/a/43170354
+            b if b.len() >= 2
+                && matches!(
+                    b[0..2],
+                    [0x78, 0x01] | [0x78, 0x5E] | [0x78, 0x9C] | [0x78, 0xDA]
+                ) =>
+            {
+                Some(Self::ZLib)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn detect_from_header_str(header: impl AsRef<str>) -> Option<Self> {
+        // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Content-Encoding#directives
+        match header.as_ref().to_ascii_lowercase().trim() {
+            "br" | "brotli" => Some(Self::Brotli),
+            "deflate" => Some(Self::ZLib),
+            "gz" | "gzip" => Some(Self::GZip),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for CompressDecompressFormat {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "brotli" => Ok(Self::Brotli),
+                "bzip2" => Ok(Self::BZip2),
+                "gzip" => Ok(Self::GZip),
+                "lz4" => Ok(Self::LZ4),
+                "xz" => Ok(Self::XZ),
+                "zlib" => Ok(Self::ZLib),
+                "zstd" => Ok(Self::Zstd),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "CompressDecompressFormat",
+                    message: Some(format!(
+                        "Invalid format '{kind}', valid formats are:  \
+                        brotli, bzip2, gzip, lz4, xz, zlib, zstd"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "CompressDecompressFormat",
+                message: None,
+            })
+        }
+    }
+}
+
+/**
+    Options for compression.
+
+    The level defaults to the best possible compression, except for zstd and xz,
+    where the default level of the format is used instead, since their highest
+    levels are very slow and use a lot of memory. The window size defaults to
+    the default window size of the format.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompressDecompressOptions {
+    pub level: Option<u32>,
+    pub window: Option<u32>,
+}
+
+impl CompressDecompressOptions {
+    /**
+        Checks that the options are supported by the given format,
+        and that their values are within the range of that format.
+    */
+    pub fn validate(&self, format: CompressDecompressFormat) -> LuaResult<()> {
+        if let Some(level) = self.level {
+            let range = match format {
+                CompressDecompressFormat::Brotli => 0..=11,
+                CompressDecompressFormat::BZip2 => 1..=9,
+                CompressDecompressFormat::GZip
+                | CompressDecompressFormat::XZ
+                | CompressDecompressFormat::ZLib => 0..=9,
+                CompressDecompressFormat::Zstd => 1..=22,
+                CompressDecompressFormat::LZ4 => {
+                    return Err(LuaError::RuntimeError(
+                        "Compression levels are not supported for lz4".to_string(),
+                    ))
+                }
+            };
+            if !range.contains(&level) {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid compression level {level} for {} - expected {} to {}",
+                    format.name(),
+                    range.start(),
+                    range.end()
+                )));
+            }
+        }
+        if let Some(window) = self.window {
+            // NOTE: Zstd windows larger than 2^27 need the decoder to opt in
+            // to using more memory, so those are not allowed to be created here
+            let range = match format {
+                CompressDecompressFormat::Brotli => 10..=24,
+                CompressDecompressFormat::Zstd => 10..=27,
+                _ => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Window sizes are only supported for brotli and zstd, not {}",
+                        format.name()
+                    )))
+                }
+            };
+            if !range.contains(&window) {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid window size {window} for {} - expected {} to {}",
+                    format.name(),
+                    range.start(),
+                    range.end()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn quality(&self) -> Level {
+        match self.level {
+            Some(level) => Level::Precise(level as i32),
+            None => Level::Best,
+        }
+    }
+
+    fn brotli_params(&self) -> BrotliParams {
+        match self.window {
+            Some(window) => BrotliParams::default().window_size(window as i32),
+            None => BrotliParams::default(),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for CompressDecompressOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Table(tab) => Ok(Self {
+                level: tab.raw_get("level")?,
+                window: tab.raw_get("window")?,
+            }),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "CompressDecompressOptions",
+                message: Some(format!(
+                    "Invalid options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+pub async fn compress<'lua>(
+    format: CompressDecompressFormat,
+    source: impl AsRef<[u8]>,
+    options: CompressDecompressOptions,
+) -> LuaResult<Vec<u8>> {
+    options.validate(format)?;
+
+    if let CompressDecompressFormat::LZ4 = format {
+        let source = source.as_ref().to_vec();
+        return task::spawn_blocking(move || compress_prepend_size(&source))
+            .await
+            .into_lua_err();
+    }
+    if format.is_blocking() {
+        let source = source.as_ref().to_vec();
+        return task::spawn_blocking(move || {
+            let mut stream = CompressDecompressStream::compressor(format, options)?;
+            let mut bytes = stream.write(&source)?;
+            bytes.extend(stream.finish()?);
+            Ok(bytes)
+        })
+        .await
+        .into_lua_err()?;
+    }
+
+    let mut bytes = Vec::new();
+    let reader = BufReader::new(source.as_ref());
+
+    match format {
+        CompressDecompressFormat::Brotli => {
+            let mut encoder = BrotliEncoder::with_quality_and_params(
+                reader,
+                options.quality(),
+                options.brotli_params(),
+            );
+            copy(&mut encoder, &mut bytes).await?;
+        }
+        CompressDecompressFormat::GZip => {
+            let mut encoder = GzipEncoder::with_quality(reader, options.quality());
+            copy(&mut encoder, &mut bytes).await?;
+        }
+        CompressDecompressFormat::ZLib => {
+            let mut encoder = ZlibEncoder::with_quality(reader, options.quality());
+            copy(&mut encoder, &mut bytes).await?;
+        }
+        _ => unreachable!("blocking formats are handled above"),
+    }
+
+    Ok(bytes)
+}
+
+/**
+    Decompresses the given data using the format detected from its magic bytes.
+*/
+pub async fn decompress_detected(source: impl AsRef<[u8]>) -> LuaResult<Vec<u8>> {
+    let source = source.as_ref();
+    let Some(format) = CompressDecompressFormat::detect_from_bytes(source) else {
+        let header = source
+            .iter()
+            .take(8)
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        return Err(LuaError::RuntimeError(format!(
+            "Failed to detect compression format - data starts with bytes [{header}], \
+            which do not match the magic bytes of any supported format \
+            (brotli and lz4 data from serde.compress must be \
+            decompressed using an explicit format instead)"
+        )));
+    };
+    decompress(format, source).await.map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Detected {} compression, but failed to decompress - {e}",
+            format.name()
+        ))
+    })
+}
+
+pub async fn decompress<'lua>(
+    format: CompressDecompressFormat,
+    source: impl AsRef<[u8]>,
+) -> LuaResult<Vec<u8>> {
+    if let CompressDecompressFormat::LZ4 = format {
+        let source = source.as_ref().to_vec();
+        return task::spawn_blocking(move || decompress_size_prepended(&source))
+            .await
+            .into_lua_err()?
+            .into_lua_err();
+    }
+    if format.is_blocking() {
+        let source = source.as_ref().to_vec();
+        return task::spawn_blocking(move || {
+            let mut stream = CompressDecompressStream::decompressor(format)?;
+            let mut bytes = stream.write(&source)?;
+            bytes.extend(stream.finish()?);
+            Ok(bytes)
+        })
+        .await
+        .into_lua_err()?;
+    }
+
+    let mut bytes = Vec::new();
+    let reader = BufReader::new(source.as_ref());
+
+    match format {
+        CompressDecompressFormat::Brotli => {
+            let mut decoder = BrotliDecoder::new(reader);
+            copy(&mut decoder, &mut bytes).await?;
+        }
+        CompressDecompressFormat::GZip => {
+            let mut decoder = GzipDecoder::new(reader);
+            copy(&mut decoder, &mut bytes).await?;
+        }
+        CompressDecompressFormat::ZLib => {
+            let mut decoder = ZlibDecoder::new(reader);
+            copy(&mut decoder, &mut bytes).await?;
+        }
+        _ => unreachable!("blocking formats are handled above"),
+    }
+
+    Ok(bytes)
+}
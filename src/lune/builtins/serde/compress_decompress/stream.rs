@@ -0,0 +1,290 @@
+use std::io::Write;
+
+use brotli::{CompressorWriter as BrotliWriter, DecompressorWriter as BrotliReader};
+use bzip2::{write::BzEncoder, Decompress as BzDecompress, Status as BzStatus};
+use flate2::{
+    write::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder},
+    Compression,
+};
+use lz4_flex::{compress_prepend_size, decompress_size_prepended};
+use mlua::prelude::*;
+use xz2::write::{XzDecoder, XzEncoder};
+use zstd::stream::{raw::Decoder as ZstdRawDecoder, write::Encoder as ZstdEncoder, zio};
+
+use super::{CompressDecompressFormat, CompressDecompressOptions};
+
+const BROTLI_BUFFER_SIZE: usize = 4096;
+const BZIP2_BUFFER_SIZE: usize = 32 * 1024;
+const BROTLI_BEST_QUALITY: u32 = 11;
+const BROTLI_DEFAULT_WINDOW: u32 = 22;
+const XZ_DEFAULT_LEVEL: u32 = 6;
+// NOTE: Level 0 means the default level for zstd
+const ZSTD_DEFAULT_LEVEL: i32 = 0;
+
+type ZstdDecoder = zio::Writer<Vec<u8>, ZstdRawDecoder<'static>>;
+
+enum StreamCodec {
+    BrotliEncoder(Box<BrotliWriter<Vec<u8>>>),
+    BrotliDecoder(Box<BrotliReader<Vec<u8>>>),
+    GzipEncoder(GzEncoder<Vec<u8>>),
+    GzipDecoder(GzDecoder<Vec<u8>>),
+    ZlibEncoder(ZlibEncoder<Vec<u8>>),
+    ZlibDecoder(ZlibDecoder<Vec<u8>>),
+    Bzip2Encoder(BzEncoder<Vec<u8>>),
+    Bzip2Decoder(Box<Bzip2Decoder>),
+    XzEncoder(XzEncoder<Vec<u8>>),
+    XzDecoder(XzDecoder<Vec<u8>>),
+    ZstdEncoder(ZstdEncoder<'static, Vec<u8>>),
+    ZstdDecoder(Box<ZstdDecoder>),
+    // NOTE: The lz4 format used by serde.compress has the total size of the
+    // data prepended to it, so it can only be processed once all data is known
+    Lz4Encoder(Vec<u8>),
+    Lz4Decoder(Vec<u8>),
+}
+
+/**
+    A compressor or decompressor that processes data in chunks.
+
+    Each chunk that is written returns any output that is ready so far,
+    and finishing the stream returns the remaining output, meaning that
+    all returned strings concatenated together are the full output.
+*/
+pub struct CompressDecompressStream {
+    format: CompressDecompressFormat,
+    codec: Option<StreamCodec>,
+}
+
+impl CompressDecompressStream {
+    pub fn compressor(
+        format: CompressDecompressFormat,
+        options: CompressDecompressOptions,
+    ) -> LuaResult<Self> {
+        options.validate(format)?;
+        let codec = match format {
+            CompressDecompressFormat::Brotli => {
+                StreamCodec::BrotliEncoder(Box::new(BrotliWriter::new(
+                    Vec::new(),
+                    BROTLI_BUFFER_SIZE,
+                    options.level.unwrap_or(BROTLI_BEST_QUALITY),
+                    options.window.unwrap_or(BROTLI_DEFAULT_WINDOW),
+                )))
+            }
+            CompressDecompressFormat::GZip => {
+                StreamCodec::GzipEncoder(GzEncoder::new(Vec::new(), flate2_level(options)))
+            }
+            CompressDecompressFormat::ZLib => {
+                StreamCodec::ZlibEncoder(ZlibEncoder::new(Vec::new(), flate2_level(options)))
+            }
+            CompressDecompressFormat::BZip2 => StreamCodec::Bzip2Encoder(BzEncoder::new(
+                Vec::new(),
+                match options.level {
+                    Some(level) => bzip2::Compression::new(level),
+                    None => bzip2::Compression::best(),
+                },
+            )),
+            CompressDecompressFormat::XZ => StreamCodec::XzEncoder(XzEncoder::new(
+                Vec::new(),
+                options.level.unwrap_or(XZ_DEFAULT_LEVEL),
+            )),
+            CompressDecompressFormat::Zstd => {
+                let level = options
+                    .level
+                    .map_or(ZSTD_DEFAULT_LEVEL, |level| level as i32);
+                let mut encoder = ZstdEncoder::new(Vec::new(), level).into_lua_err()?;
+                if let Some(window) = options.window {
+                    encoder.window_log(window).into_lua_err()?;
+                }
+                StreamCodec::ZstdEncoder(encoder)
+            }
+            CompressDecompressFormat::LZ4 => StreamCodec::Lz4Encoder(Vec::new()),
+        };
+        Ok(Self {
+            format,
+            codec: Some(codec),
+        })
+    }
+
+    pub fn decompressor(format: CompressDecompressFormat) -> LuaResult<Self> {
+        let codec = match format {
+            CompressDecompressFormat::Brotli => StreamCodec::BrotliDecoder(Box::new(
+                BrotliReader::new(Vec::new(), BROTLI_BUFFER_SIZE),
+            )),
+            CompressDecompressFormat::GZip => StreamCodec::GzipDecoder(GzDecoder::new(Vec::new())),
+            CompressDecompressFormat::ZLib => {
+                StreamCodec::ZlibDecoder(ZlibDecoder::new(Vec::new()))
+            }
+            CompressDecompressFormat::BZip2 => {
+                StreamCodec::Bzip2Decoder(Box::new(Bzip2Decoder::new()))
+            }
+            CompressDecompressFormat::XZ => StreamCodec::XzDecoder(XzDecoder::new(Vec::new())),
+            CompressDecompressFormat::Zstd => StreamCodec::ZstdDecoder(Box::new(zio::Writer::new(
+                Vec::new(),
+                ZstdRawDecoder::new().into_lua_err()?,
+            ))),
+            CompressDecompressFormat::LZ4 => StreamCodec::Lz4Decoder(Vec::new()),
+        };
+        Ok(Self {
+            format,
+            codec: Some(codec),
+        })
+    }
+
+    /**
+        Writes a chunk of data to the stream, returning any output that is ready.
+    */
+    pub fn write(&mut self, chunk: &[u8]) -> LuaResult<Vec<u8>> {
+        let format = self.format;
+        let codec = self.codec.as_mut().ok_or_else(|| {
+            LuaError::RuntimeError(format!(
+                "Failed to write to {} stream - stream has already been finished",
+                format.name()
+            ))
+        })?;
+        let output = match codec {
+            StreamCodec::BrotliEncoder(w) => {
+                write_and_take(w.as_mut(), chunk, BrotliWriter::get_mut)
+            }
+            StreamCodec::BrotliDecoder(w) => {
+                write_and_take(w.as_mut(), chunk, BrotliReader::get_mut)
+            }
+            StreamCodec::GzipEncoder(w) => write_and_take(w, chunk, GzEncoder::get_mut),
+            StreamCodec::GzipDecoder(w) => write_and_take(w, chunk, GzDecoder::get_mut),
+            StreamCodec::ZlibEncoder(w) => write_and_take(w, chunk, ZlibEncoder::get_mut),
+            StreamCodec::ZlibDecoder(w) => write_and_take(w, chunk, ZlibDecoder::get_mut),
+            StreamCodec::Bzip2Encoder(w) => write_and_take(w, chunk, BzEncoder::get_mut),
+            StreamCodec::Bzip2Decoder(d) => d.write(chunk),
+            StreamCodec::XzEncoder(w) => write_and_take(w, chunk, XzEncoder::get_mut),
+            StreamCodec::XzDecoder(w) => write_and_take(w, chunk, XzDecoder::get_mut),
+            StreamCodec::ZstdEncoder(w) => write_and_take(w, chunk, ZstdEncoder::get_mut),
+            StreamCodec::ZstdDecoder(w) => {
+                write_and_take(w.as_mut(), chunk, ZstdDecoder::writer_mut)
+            }
+            StreamCodec::Lz4Encoder(buf) | StreamCodec::Lz4Decoder(buf) => {
+                buf.extend_from_slice(chunk);
+                Ok(Vec::new())
+            }
+        };
+        output.map_err(|e| self.error(e))
+    }
+
+    /**
+        Finishes the stream, returning all of the remaining output.
+
+        Finishing a stream that has already been finished returns no output.
+    */
+    pub fn finish(&mut self) -> LuaResult<Vec<u8>> {
+        let Some(codec) = self.codec.take() else {
+            return Ok(Vec::new());
+        };
+        let output = match codec {
+            StreamCodec::BrotliEncoder(w) => Ok(w.into_inner()),
+            StreamCodec::BrotliDecoder(w) => w.into_inner().map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "brotli stream ended unexpectedly",
+                )
+            }),
+            StreamCodec::GzipEncoder(w) => w.finish(),
+            StreamCodec::GzipDecoder(w) => w.finish(),
+            StreamCodec::ZlibEncoder(w) => w.finish(),
+            StreamCodec::ZlibDecoder(w) => w.finish(),
+            StreamCodec::Bzip2Encoder(w) => w.finish(),
+            StreamCodec::Bzip2Decoder(mut d) => d.finish(),
+            StreamCodec::XzEncoder(w) => w.finish(),
+            StreamCodec::XzDecoder(mut w) => w.finish(),
+            StreamCodec::ZstdEncoder(w) => w.finish(),
+            StreamCodec::ZstdDecoder(mut w) => w.finish().map(|_| w.into_inner().0),
+            StreamCodec::Lz4Encoder(buf) => Ok(compress_prepend_size(&buf)),
+            StreamCodec::Lz4Decoder(buf) => decompress_size_prepended(&buf)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())),
+        };
+        output.map_err(|e| self.error(e))
+    }
+
+    fn error(&self, e: std::io::Error) -> LuaError {
+        LuaError::RuntimeError(format!("Invalid {} stream - {e}", self.format.name()))
+    }
+}
+
+impl LuaUserData for CompressDecompressStream {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("write", |lua, this, chunk: LuaString| {
+            lua.create_string(this.write(chunk.as_bytes())?)
+        });
+
+        methods.add_method_mut("finish", |lua, this, ()| lua.create_string(this.finish()?));
+    }
+}
+
+fn flate2_level(options: CompressDecompressOptions) -> Compression {
+    match options.level {
+        Some(level) => Compression::new(level),
+        None => Compression::best(),
+    }
+}
+
+fn write_and_take<W: Write>(
+    writer: &mut W,
+    chunk: &[u8],
+    inner: fn(&mut W) -> &mut Vec<u8>,
+) -> std::io::Result<Vec<u8>> {
+    writer.write_all(chunk)?;
+    Ok(std::mem::take(inner(writer)))
+}
+
+/**
+    A bzip2 decoder that writes all of its output into a buffer.
+
+    This is used instead of the writer from the bzip2 crate, since
+    that writer never stops trying to finish a truncated stream.
+*/
+struct Bzip2Decoder {
+    inner: BzDecompress,
+    output: Vec<u8>,
+    done: bool,
+}
+
+impl Bzip2Decoder {
+    fn new() -> Self {
+        Self {
+            inner: BzDecompress::new(false),
+            output: Vec::new(),
+            done: false,
+        }
+    }
+
+    fn write(&mut self, mut chunk: &[u8]) -> std::io::Result<Vec<u8>> {
+        while !self.done {
+            self.output.reserve(BZIP2_BUFFER_SIZE);
+            let before_in = self.inner.total_in();
+            let before_out = self.inner.total_out();
+            let status = self
+                .inner
+                .decompress_vec(chunk, &mut self.output)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            let consumed = (self.inner.total_in() - before_in) as usize;
+            let produced = self.inner.total_out() - before_out;
+            chunk = &chunk[consumed..];
+            if let BzStatus::StreamEnd = status {
+                self.done = true;
+            } else if (consumed == 0 && produced == 0)
+                || (chunk.is_empty() && self.output.len() < self.output.capacity())
+            {
+                break;
+            }
+        }
+        Ok(std::mem::take(&mut self.output))
+    }
+
+    fn finish(&mut self) -> std::io::Result<Vec<u8>> {
+        let output = self.write(&[])?;
+        if self.done {
+            Ok(output)
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "bzip2 stream ended unexpectedly",
+            ))
+        }
+    }
+}
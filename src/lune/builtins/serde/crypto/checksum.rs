@@ -0,0 +1,67 @@
+use xxhash_rust::{xxh3::Xxh3Default, xxh64::Xxh64 as Xxh64Hasher};
+
+/**
+    A non-cryptographic checksum.
+
+    These are much faster than the cryptographic hash functions, but
+    must never be relied on where collisions could be made on purpose.
+
+    Checksums are output as big-endian bytes, which matches the
+    hex strings that most other tools display for these checksums.
+*/
+pub trait Checksum: Clone + Default {
+    fn update(&mut self, data: &[u8]);
+    fn finalize(&self) -> Vec<u8>;
+}
+
+#[derive(Clone, Default)]
+pub struct Crc32(crc32fast::Hasher);
+
+impl Checksum for Crc32 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        self.0.clone().finalize().to_be_bytes().to_vec()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Xxh64(Xxh64Hasher);
+
+impl Checksum for Xxh64 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        self.0.digest().to_be_bytes().to_vec()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Xxh3_64(Xxh3Default);
+
+impl Checksum for Xxh3_64 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        self.0.digest().to_be_bytes().to_vec()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Xxh3_128(Xxh3Default);
+
+impl Checksum for Xxh3_128 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        self.0.digest128().to_be_bytes().to_vec()
+    }
+}
@@ -0,0 +1,102 @@
+use aes_gcm::{
+    aead::{generic_array::typenum::Unsigned, Aead, KeyInit, Nonce, Payload},
+    Aes256Gcm,
+};
+use chacha20poly1305::ChaCha20Poly1305;
+use mlua::prelude::*;
+
+use super::{algorithm_from_lua, CryptoError, CryptoResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherKind {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl CipherKind {
+    pub const NAMES: &'static [&'static str] = &["aes256gcm", "chacha20poly1305"];
+    const ALL: &'static [Self] = &[Self::Aes256Gcm, Self::ChaCha20Poly1305];
+
+    /**
+        Encrypts and authenticates the given plaintext, returning
+        the ciphertext with the authentication tag appended to it.
+    */
+    pub fn encrypt(
+        &self,
+        key: impl AsRef<[u8]>,
+        nonce: impl AsRef<[u8]>,
+        plaintext: impl AsRef<[u8]>,
+        aad: Option<impl AsRef<[u8]>>,
+    ) -> CryptoResult<Vec<u8>> {
+        let payload = Payload {
+            msg: plaintext.as_ref(),
+            aad: aad.as_ref().map(AsRef::as_ref).unwrap_or_default(),
+        };
+        match self {
+            Self::Aes256Gcm => {
+                let (cipher, nonce) = init::<Aes256Gcm>(key.as_ref(), nonce.as_ref())?;
+                cipher.encrypt(nonce, payload)
+            }
+            Self::ChaCha20Poly1305 => {
+                let (cipher, nonce) = init::<ChaCha20Poly1305>(key.as_ref(), nonce.as_ref())?;
+                cipher.encrypt(nonce, payload)
+            }
+        }
+        .map_err(|_| CryptoError::Encryption)
+    }
+
+    /**
+        Verifies and decrypts the given ciphertext, which must
+        have its authentication tag appended to it.
+    */
+    pub fn decrypt(
+        &self,
+        key: impl AsRef<[u8]>,
+        nonce: impl AsRef<[u8]>,
+        ciphertext: impl AsRef<[u8]>,
+        aad: Option<impl AsRef<[u8]>>,
+    ) -> CryptoResult<Vec<u8>> {
+        let payload = Payload {
+            msg: ciphertext.as_ref(),
+            aad: aad.as_ref().map(AsRef::as_ref).unwrap_or_default(),
+        };
+        match self {
+            Self::Aes256Gcm => {
+                let (cipher, nonce) = init::<Aes256Gcm>(key.as_ref(), nonce.as_ref())?;
+                cipher.decrypt(nonce, payload)
+            }
+            Self::ChaCha20Poly1305 => {
+                let (cipher, nonce) = init::<ChaCha20Poly1305>(key.as_ref(), nonce.as_ref())?;
+                cipher.decrypt(nonce, payload)
+            }
+        }
+        .map_err(|_| CryptoError::Decryption)
+    }
+}
+
+fn init<'n, C: Aead + KeyInit>(key: &[u8], nonce: &'n [u8]) -> CryptoResult<(C, &'n Nonce<C>)> {
+    // NOTE: Both of these lengths must be checked manually, creating
+    // a nonce from a slice of the wrong length would panic otherwise
+    if key.len() != C::KeySize::USIZE {
+        return Err(CryptoError::InvalidLength {
+            kind: "key",
+            expected: C::KeySize::USIZE,
+            got: key.len(),
+        });
+    }
+    if nonce.len() != C::NonceSize::USIZE {
+        return Err(CryptoError::InvalidLength {
+            kind: "nonce",
+            expected: C::NonceSize::USIZE,
+            got: nonce.len(),
+        });
+    }
+    let cipher = C::new_from_slice(key).map_err(|_| CryptoError::InvalidKeyLength)?;
+    Ok((cipher, Nonce::<C>::from_slice(nonce)))
+}
+
+impl<'lua> FromLua<'lua> for CipherKind {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        algorithm_from_lua(value, "CipherKind", "cipher", Self::NAMES, Self::ALL)
+    }
+}
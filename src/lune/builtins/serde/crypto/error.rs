@@ -0,0 +1,64 @@
+use mlua::prelude::*;
+
+use thiserror::Error;
+
+use super::EncodingKind;
+
+pub type CryptoResult<T, E = CryptoError> = Result<T, E>;
+
+#[derive(Debug, Clone, Error)]
+pub enum CryptoError {
+    #[error(
+        "invalid encoding '{0}', valid encodings are: {}",
+        EncodingKind::NAMES.join(", ")
+    )]
+    InvalidEncoding(String),
+    #[error("invalid {kind} algorithm '{name}', valid algorithms are: {}", valid.join(", "))]
+    InvalidAlgorithm {
+        kind: &'static str,
+        name: String,
+        valid: &'static [&'static str],
+    },
+    #[error("digest is not valid utf8, use the hex, base64 or binary encoding instead")]
+    InvalidUtf8,
+    #[error("invalid key length")]
+    InvalidKeyLength,
+    #[error("invalid {kind} length, expected {expected} bytes but got {got}")]
+    InvalidLength {
+        kind: &'static str,
+        expected: usize,
+        got: usize,
+    },
+    #[error("invalid {kind} length, expected at most {max} bytes but got {got}")]
+    LengthTooLarge {
+        kind: &'static str,
+        max: usize,
+        got: usize,
+    },
+    #[error("invalid parameters - {0}")]
+    InvalidParams(String),
+    #[error("invalid password hash - {0}")]
+    InvalidPasswordHash(argon2::password_hash::Error),
+    #[error("invalid {kind} key - {message}")]
+    InvalidKey { kind: &'static str, message: String },
+    #[error("key does not contain a private key")]
+    MissingPrivateKey,
+    #[error("failed to generate random bytes - {0}")]
+    Random(String),
+    #[error("encryption failed")]
+    Encryption,
+    #[error("decryption failed, the ciphertext, key, nonce or associated data is invalid")]
+    Decryption,
+    #[error("crypto state was poisoned by a previous error")]
+    Poisoned,
+    #[error("failed to decode hex string - {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("failed to decode base64 string - {0}")]
+    Base64(#[from] base64::DecodeError),
+}
+
+impl From<CryptoError> for LuaError {
+    fn from(value: CryptoError) -> Self {
+        LuaError::runtime(value.to_string())
+    }
+}
@@ -0,0 +1,265 @@
+use argon2::{
+    password_hash::{
+        rand_core::OsRng, Error as PasswordHashError, PasswordHash, PasswordHasher,
+        PasswordVerifier, SaltString,
+    },
+    Algorithm as Argon2Algorithm, Argon2, Params as Argon2Params, Version as Argon2Version,
+};
+use mlua::prelude::*;
+use pbkdf2::{Params as Pbkdf2Params, Pbkdf2};
+use scrypt::{Params as ScryptParams, Scrypt};
+
+use super::{algorithm_from_lua, check_output_length, CryptoError, CryptoResult};
+
+const DEFAULT_OUTPUT_LENGTH: usize = 32;
+
+// NOTE: The pbkdf2 crate defaults to much fewer rounds than the
+// current OWASP recommendation, so we use that recommendation here
+const DEFAULT_PBKDF2_ROUNDS: u32 = 600_000;
+
+// NOTE: Argon2 and scrypt allocate all of their memory at once, and a failed
+// allocation aborts instead of throwing an error, so their costs are limited
+const MAX_MEMORY: u64 = 1024 * 1024 * 1024;
+const MAX_PARALLELISM: u32 = 255;
+const MAX_LOG_N: u8 = 24;
+const MAX_BLOCK_SIZE: u32 = 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PasswordAlgorithm {
+    #[default]
+    Argon2id,
+    Scrypt,
+    Pbkdf2,
+}
+
+impl PasswordAlgorithm {
+    pub const NAMES: &'static [&'static str] = &["argon2id", "scrypt", "pbkdf2"];
+    const ALL: &'static [Self] = &[Self::Argon2id, Self::Scrypt, Self::Pbkdf2];
+}
+
+impl<'lua> FromLua<'lua> for PasswordAlgorithm {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        algorithm_from_lua(
+            value,
+            "PasswordAlgorithm",
+            "password hashing",
+            Self::NAMES,
+            Self::ALL,
+        )
+    }
+}
+
+/**
+    Cost parameters for the password hashing functions.
+
+    Any parameter that is not given uses the default for the
+    respective algorithm, and parameters not relevant to the
+    chosen algorithm are ignored.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct KdfOptions {
+    pub algorithm: PasswordAlgorithm,
+    pub memory_cost: Option<u32>,
+    pub time_cost: Option<u32>,
+    pub parallelism: Option<u32>,
+    pub log_n: Option<u8>,
+    pub block_size: Option<u32>,
+    pub iterations: Option<u32>,
+    pub length: Option<usize>,
+}
+
+impl KdfOptions {
+    /**
+        Checks that the cost parameters that were given do not use more memory
+        than can be allocated, regardless of which algorithm they will be used for.
+    */
+    fn check_costs(&self) -> CryptoResult<()> {
+        if let Some(parallelism) = self.parallelism {
+            check_cost("parallelism", parallelism.into(), MAX_PARALLELISM.into())?;
+        }
+        if let Some(memory_cost) = self.memory_cost {
+            check_cost("memoryCost", memory_cost.into(), MAX_MEMORY / 1024)?;
+        }
+        if let Some(log_n) = self.log_n {
+            check_cost("logN", log_n.into(), MAX_LOG_N.into())?;
+        }
+        if let Some(block_size) = self.block_size {
+            check_cost("blockSize", block_size.into(), MAX_BLOCK_SIZE.into())?;
+        }
+        if self.log_n.is_some() || self.block_size.is_some() {
+            let log_n = self.log_n.unwrap_or(ScryptParams::RECOMMENDED_LOG_N);
+            let block_size = self.block_size.unwrap_or(ScryptParams::RECOMMENDED_R);
+            let memory = (128 * u64::from(block_size)) << log_n;
+            if memory > MAX_MEMORY {
+                return Err(CryptoError::InvalidParams(format!(
+                    "'logN' and 'blockSize' are too large, expected them \
+                    to use at most {MAX_MEMORY} bytes of memory but got {memory}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn argon2(&self) -> CryptoResult<Argon2<'static>> {
+        let params = Argon2Params::new(
+            self.memory_cost.unwrap_or(Argon2Params::DEFAULT_M_COST),
+            self.time_cost.unwrap_or(Argon2Params::DEFAULT_T_COST),
+            self.parallelism.unwrap_or(Argon2Params::DEFAULT_P_COST),
+            Some(self.length.unwrap_or(DEFAULT_OUTPUT_LENGTH)),
+        )
+        .map_err(|e| CryptoError::InvalidParams(e.to_string()))?;
+        Ok(Argon2::new(
+            Argon2Algorithm::Argon2id,
+            Argon2Version::V0x13,
+            params,
+        ))
+    }
+
+    fn scrypt(&self, length: usize) -> CryptoResult<ScryptParams> {
+        ScryptParams::new(
+            self.log_n.unwrap_or(ScryptParams::RECOMMENDED_LOG_N),
+            self.block_size.unwrap_or(ScryptParams::RECOMMENDED_R),
+            self.parallelism.unwrap_or(ScryptParams::RECOMMENDED_P),
+            length,
+        )
+        .map_err(|e| CryptoError::InvalidParams(e.to_string()))
+    }
+
+    fn pbkdf2(&self) -> Pbkdf2Params {
+        Pbkdf2Params {
+            rounds: self.iterations.unwrap_or(DEFAULT_PBKDF2_ROUNDS),
+            output_length: self.length.unwrap_or(DEFAULT_OUTPUT_LENGTH),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for KdfOptions {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        // Nil means default options, table means custom options
+        if let LuaValue::Nil = value {
+            return Ok(Self::default());
+        } else if let LuaValue::Table(tab) = &value {
+            let algorithm = match tab.raw_get::<_, LuaValue>("algorithm")? {
+                LuaValue::Nil => PasswordAlgorithm::default(),
+                value => PasswordAlgorithm::from_lua(value, lua)?,
+            };
+            let options = Self {
+                algorithm,
+                memory_cost: tab.raw_get("memoryCost")?,
+                time_cost: tab.raw_get("timeCost")?,
+                parallelism: tab.raw_get("parallelism")?,
+                log_n: tab.raw_get("logN")?,
+                block_size: tab.raw_get("blockSize")?,
+                iterations: tab.raw_get("iterations")?,
+                length: tab
+                    .raw_get::<_, Option<usize>>("length")?
+                    .map(|length| check_output_length("kdf output", length))
+                    .transpose()?,
+            };
+            options.check_costs()?;
+            return Ok(options);
+        }
+        // Anything else is invalid
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "KdfOptions",
+            message: Some(format!(
+                "Invalid kdf options - expected table or nil, got {}",
+                value.type_name()
+            )),
+        })
+    }
+}
+
+fn check_cost(name: &'static str, cost: u64, max: u64) -> CryptoResult<()> {
+    if cost > max {
+        Err(CryptoError::InvalidParams(format!(
+            "'{name}' is too large, expected at most {max} but got {cost}"
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/**
+    Derives a raw key from the given password and salt using Argon2id.
+*/
+pub fn argon2id(
+    password: impl AsRef<[u8]>,
+    salt: impl AsRef<[u8]>,
+    options: &KdfOptions,
+) -> CryptoResult<Vec<u8>> {
+    let mut derived = vec![0; options.length.unwrap_or(DEFAULT_OUTPUT_LENGTH)];
+    options
+        .argon2()?
+        .hash_password_into(password.as_ref(), salt.as_ref(), &mut derived)
+        .map_err(|e| CryptoError::InvalidParams(e.to_string()))?;
+    Ok(derived)
+}
+
+/**
+    Derives a raw key from the given password and salt using scrypt.
+*/
+pub fn scrypt(
+    password: impl AsRef<[u8]>,
+    salt: impl AsRef<[u8]>,
+    options: &KdfOptions,
+) -> CryptoResult<Vec<u8>> {
+    // NOTE: The length stored in scrypt params is only used for password hashes
+    // and is limited to 64 bytes, raw keys instead use the length of the output
+    let params = options.scrypt(ScryptParams::RECOMMENDED_LEN)?;
+    let mut derived = vec![0; options.length.unwrap_or(DEFAULT_OUTPUT_LENGTH)];
+    scrypt::scrypt(password.as_ref(), salt.as_ref(), &params, &mut derived)
+        .map_err(|e| CryptoError::InvalidParams(e.to_string()))?;
+    Ok(derived)
+}
+
+/**
+    Hashes the given password using a randomly generated salt,
+    returning the hash as a string in the PHC string format.
+*/
+pub fn hash_password(password: impl AsRef<[u8]>, options: &KdfOptions) -> CryptoResult<String> {
+    let password = password.as_ref();
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = match options.algorithm {
+        PasswordAlgorithm::Argon2id => options.argon2()?.hash_password(password, &salt),
+        PasswordAlgorithm::Scrypt => {
+            let params = options.scrypt(options.length.unwrap_or(DEFAULT_OUTPUT_LENGTH))?;
+            Scrypt.hash_password_customized(password, None, None, params, &salt)
+        }
+        PasswordAlgorithm::Pbkdf2 => {
+            Pbkdf2.hash_password_customized(password, None, None, options.pbkdf2(), &salt)
+        }
+    };
+    hash.map(|hash| hash.to_string())
+        .map_err(|e| CryptoError::InvalidParams(e.to_string()))
+}
+
+/**
+    Verifies the given password against a hash in the PHC string format.
+
+    The algorithm and its parameters are read from the hash itself, and
+    any of the algorithms supported by [`hash_password`] may be used.
+*/
+pub fn verify_password(password: impl AsRef<[u8]>, hash: impl AsRef<str>) -> CryptoResult<bool> {
+    let hash = PasswordHash::new(hash.as_ref()).map_err(CryptoError::InvalidPasswordHash)?;
+    // NOTE: Costs stored in the hash are checked the same way as given costs,
+    // since hashes may come from anywhere and contain any costs at all
+    KdfOptions {
+        memory_cost: hash.params.get_decimal("m"),
+        parallelism: hash.params.get_decimal("p"),
+        log_n: hash
+            .params
+            .get_decimal("ln")
+            .map(|log_n| log_n.min(u8::MAX.into()) as u8),
+        block_size: hash.params.get_decimal("r"),
+        ..Default::default()
+    }
+    .check_costs()?;
+    let verifiers: &[&dyn PasswordVerifier] = &[&Argon2::default(), &Scrypt, &Pbkdf2];
+    match hash.verify_password(verifiers, password) {
+        Ok(()) => Ok(true),
+        Err(PasswordHashError::Password) => Ok(false),
+        Err(e) => Err(CryptoError::InvalidPasswordHash(e)),
+    }
+}
@@ -0,0 +1,361 @@
+use std::fmt::Display;
+
+use ed25519_dalek as ed25519;
+use mlua::prelude::*;
+use p256::{
+    ecdsa::{
+        self as p256_ecdsa,
+        signature::{Signer as _, Verifier as _},
+    },
+    pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding},
+};
+use rand_core::OsRng;
+
+use super::{algorithm_from_lua, verify_encoded, CryptoError, CryptoResult, EncodingKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    Ed25519,
+    P256,
+}
+
+impl KeyAlgorithm {
+    pub const NAMES: &'static [&'static str] = &["ed25519", "p256"];
+    const ALL: &'static [Self] = &[Self::Ed25519, Self::P256];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ed25519 => "ed25519",
+            Self::P256 => "p256",
+        }
+    }
+
+    fn invalid_key(&self, message: impl Display) -> CryptoError {
+        CryptoError::InvalidKey {
+            kind: self.name(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for KeyAlgorithm {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        algorithm_from_lua(value, "KeyAlgorithm", "key", Self::NAMES, Self::ALL)
+    }
+}
+
+/**
+    The format to import or export a key in.
+
+    PEM and DER use PKCS#8 for private keys and SPKI for public keys,
+    raw keys are the plain key bytes in one of the usual encodings.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyFormat {
+    #[default]
+    Pem,
+    Der,
+    Raw(EncodingKind),
+}
+
+impl<'lua> FromLua<'lua> for KeyFormat {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "pem" => return Ok(Self::Pem),
+                "der" => return Ok(Self::Der),
+                _ => {}
+            }
+        }
+        EncodingKind::from_lua(value, lua).map(Self::Raw)
+    }
+}
+
+/**
+    An Ed25519 or ECDSA P-256 key pair.
+
+    The private key is optional, key pairs created from only a
+    public key can verify signatures but not create them.
+*/
+#[derive(Clone)]
+pub enum KeyPair {
+    Ed25519(ed25519::VerifyingKey, Option<Box<ed25519::SigningKey>>),
+    P256(
+        p256_ecdsa::VerifyingKey,
+        Option<Box<p256_ecdsa::SigningKey>>,
+    ),
+}
+
+impl KeyPair {
+    pub fn generate(algorithm: KeyAlgorithm) -> Self {
+        match algorithm {
+            KeyAlgorithm::Ed25519 => {
+                let private = ed25519::SigningKey::generate(&mut OsRng);
+                Self::Ed25519(private.verifying_key(), Some(Box::new(private)))
+            }
+            KeyAlgorithm::P256 => {
+                let private = p256_ecdsa::SigningKey::random(&mut OsRng);
+                Self::P256(*private.verifying_key(), Some(Box::new(private)))
+            }
+        }
+    }
+
+    pub fn import_private_key(
+        algorithm: KeyAlgorithm,
+        data: impl AsRef<[u8]>,
+        format: KeyFormat,
+    ) -> CryptoResult<Self> {
+        let data = data.as_ref();
+        match algorithm {
+            KeyAlgorithm::Ed25519 => {
+                let private = decode_private_key(algorithm, data, format, |raw| {
+                    Ok(ed25519::SigningKey::from_bytes(&raw_array(raw)?))
+                })?;
+                Ok(Self::Ed25519(
+                    private.verifying_key(),
+                    Some(Box::new(private)),
+                ))
+            }
+            KeyAlgorithm::P256 => {
+                let private = decode_private_key(algorithm, data, format, |raw| {
+                    p256_ecdsa::SigningKey::from_slice(raw).map_err(|e| algorithm.invalid_key(e))
+                })?;
+                Ok(Self::P256(
+                    *private.verifying_key(),
+                    Some(Box::new(private)),
+                ))
+            }
+        }
+    }
+
+    pub fn import_public_key(
+        algorithm: KeyAlgorithm,
+        data: impl AsRef<[u8]>,
+        format: KeyFormat,
+    ) -> CryptoResult<Self> {
+        let data = data.as_ref();
+        match algorithm {
+            KeyAlgorithm::Ed25519 => {
+                let public = decode_public_key(algorithm, data, format, |raw| {
+                    ed25519::VerifyingKey::from_bytes(&raw_array(raw)?)
+                        .map_err(|e| algorithm.invalid_key(e))
+                })?;
+                Ok(Self::Ed25519(public, None))
+            }
+            KeyAlgorithm::P256 => {
+                // NOTE: Raw public keys may be given in either the compressed
+                // or uncompressed SEC1 form, we always export uncompressed
+                let public = decode_public_key(algorithm, data, format, |raw| {
+                    p256_ecdsa::VerifyingKey::from_sec1_bytes(raw)
+                        .map_err(|e| algorithm.invalid_key(e))
+                })?;
+                Ok(Self::P256(public, None))
+            }
+        }
+    }
+
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        match self {
+            Self::Ed25519(..) => KeyAlgorithm::Ed25519,
+            Self::P256(..) => KeyAlgorithm::P256,
+        }
+    }
+
+    pub fn has_private_key(&self) -> bool {
+        matches!(self, Self::Ed25519(_, Some(_)) | Self::P256(_, Some(_)))
+    }
+
+    pub fn public_key(&self) -> Self {
+        match self {
+            Self::Ed25519(public, _) => Self::Ed25519(*public, None),
+            Self::P256(public, _) => Self::P256(*public, None),
+        }
+    }
+
+    pub fn export_private_key(&self, format: KeyFormat) -> CryptoResult<Vec<u8>> {
+        let algorithm = self.algorithm();
+        match self {
+            Self::Ed25519(_, Some(private)) => {
+                encode_private_key(algorithm, private.as_ref(), format, || {
+                    private.to_bytes().to_vec()
+                })
+            }
+            Self::P256(_, Some(private)) => {
+                encode_private_key(algorithm, private.as_ref(), format, || {
+                    private.to_bytes().to_vec()
+                })
+            }
+            _ => Err(CryptoError::MissingPrivateKey),
+        }
+    }
+
+    pub fn export_public_key(&self, format: KeyFormat) -> CryptoResult<Vec<u8>> {
+        let algorithm = self.algorithm();
+        match self {
+            Self::Ed25519(public, _) => {
+                encode_public_key(algorithm, public, format, || public.to_bytes().to_vec())
+            }
+            Self::P256(public, _) => encode_public_key(algorithm, public, format, || {
+                public.to_encoded_point(false).as_bytes().to_vec()
+            }),
+        }
+    }
+
+    /**
+        Signs the given message, returning the raw signature bytes.
+
+        P-256 signatures use SHA-256 and are the fixed size `r || s`
+        concatenation, which is the same format used by JWTs (ES256).
+    */
+    pub fn sign(&self, message: impl AsRef<[u8]>) -> CryptoResult<Vec<u8>> {
+        match self {
+            Self::Ed25519(_, Some(private)) => {
+                let signature: ed25519::Signature = private.sign(message.as_ref());
+                Ok(signature.to_bytes().to_vec())
+            }
+            Self::P256(_, Some(private)) => {
+                let signature: p256_ecdsa::Signature = private.sign(message.as_ref());
+                Ok(signature.to_bytes().to_vec())
+            }
+            _ => Err(CryptoError::MissingPrivateKey),
+        }
+    }
+
+    /**
+        Verifies the given raw signature for a message.
+
+        P-256 signatures may be given either in the fixed size
+        `r || s` format or in the ASN.1 DER format used by X.509.
+    */
+    pub fn verify(&self, message: impl AsRef<[u8]>, signature: impl AsRef<[u8]>) -> bool {
+        let message = message.as_ref();
+        let signature = signature.as_ref();
+        match self {
+            Self::Ed25519(public, _) => ed25519::Signature::from_slice(signature)
+                .is_ok_and(|signature| public.verify(message, &signature).is_ok()),
+            Self::P256(public, _) => {
+                let parsed = if signature.len() == 64 {
+                    p256_ecdsa::Signature::from_slice(signature)
+                } else {
+                    p256_ecdsa::Signature::from_der(signature)
+                };
+                parsed.is_ok_and(|signature| public.verify(message, &signature).is_ok())
+            }
+        }
+    }
+}
+
+fn raw_array(raw: &[u8]) -> CryptoResult<[u8; 32]> {
+    raw.try_into().map_err(|_| CryptoError::InvalidLength {
+        kind: "key",
+        expected: 32,
+        got: raw.len(),
+    })
+}
+
+fn pem_str(algorithm: KeyAlgorithm, data: &[u8]) -> CryptoResult<&str> {
+    std::str::from_utf8(data).map_err(|_| algorithm.invalid_key("pem data is not valid utf8"))
+}
+
+fn decode_private_key<K: DecodePrivateKey>(
+    algorithm: KeyAlgorithm,
+    data: &[u8],
+    format: KeyFormat,
+    from_raw: impl FnOnce(&[u8]) -> CryptoResult<K>,
+) -> CryptoResult<K> {
+    match format {
+        KeyFormat::Pem => K::from_pkcs8_pem(pem_str(algorithm, data)?),
+        KeyFormat::Der => K::from_pkcs8_der(data),
+        KeyFormat::Raw(encoding) => return from_raw(&encoding.decode(data)?),
+    }
+    .map_err(|e| algorithm.invalid_key(e))
+}
+
+fn decode_public_key<K: DecodePublicKey>(
+    algorithm: KeyAlgorithm,
+    data: &[u8],
+    format: KeyFormat,
+    from_raw: impl FnOnce(&[u8]) -> CryptoResult<K>,
+) -> CryptoResult<K> {
+    match format {
+        KeyFormat::Pem => K::from_public_key_pem(pem_str(algorithm, data)?),
+        KeyFormat::Der => K::from_public_key_der(data),
+        KeyFormat::Raw(encoding) => return from_raw(&encoding.decode(data)?),
+    }
+    .map_err(|e| algorithm.invalid_key(e))
+}
+
+fn encode_private_key<K: EncodePrivateKey>(
+    algorithm: KeyAlgorithm,
+    key: &K,
+    format: KeyFormat,
+    to_raw: impl FnOnce() -> Vec<u8>,
+) -> CryptoResult<Vec<u8>> {
+    match format {
+        KeyFormat::Pem => key
+            .to_pkcs8_pem(LineEnding::LF)
+            .map(|pem| pem.as_bytes().to_vec()),
+        KeyFormat::Der => key.to_pkcs8_der().map(|der| der.as_bytes().to_vec()),
+        KeyFormat::Raw(encoding) => return encoding.encode(to_raw()),
+    }
+    .map_err(|e| algorithm.invalid_key(e))
+}
+
+fn encode_public_key<K: EncodePublicKey>(
+    algorithm: KeyAlgorithm,
+    key: &K,
+    format: KeyFormat,
+    to_raw: impl FnOnce() -> Vec<u8>,
+) -> CryptoResult<Vec<u8>> {
+    match format {
+        KeyFormat::Pem => key
+            .to_public_key_pem(LineEnding::LF)
+            .map(String::into_bytes)
+            .map_err(|e| algorithm.invalid_key(e)),
+        KeyFormat::Der => key
+            .to_public_key_der()
+            .map(|der| der.into_vec())
+            .map_err(|e| algorithm.invalid_key(e)),
+        KeyFormat::Raw(encoding) => encoding.encode(to_raw()),
+    }
+}
+
+impl LuaUserData for KeyPair {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("algorithm", |_, this| Ok(this.algorithm().name()));
+        fields.add_field_method_get("hasPrivateKey", |_, this| Ok(this.has_private_key()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method(
+            "sign",
+            |lua, this, (message, encoding): (LuaString, Option<EncodingKind>)| {
+                let signature = this.sign(message)?;
+                lua.create_string(encoding.unwrap_or(EncodingKind::Binary).encode(signature)?)
+            },
+        );
+
+        methods.add_method(
+            "verify",
+            |_, this, (message, signature, encoding): (LuaString, LuaString, Option<EncodingKind>)| {
+                let encoding = encoding.unwrap_or(EncodingKind::Binary);
+                Ok(verify_encoded(signature, encoding, |signature| {
+                    Ok(this.verify(message, signature))
+                })?)
+            },
+        );
+
+        methods.add_method("publicKey", |_, this, ()| Ok(this.public_key()));
+
+        methods.add_method(
+            "exportPrivateKey",
+            |lua, this, format: Option<KeyFormat>| {
+                lua.create_string(this.export_private_key(format.unwrap_or_default())?)
+            },
+        );
+
+        methods.add_method("exportPublicKey", |lua, this, format: Option<KeyFormat>| {
+            lua.create_string(this.export_public_key(format.unwrap_or_default())?)
+        });
+    }
+}
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use hmac::{Mac as _, SimpleHmac};
+use mlua::prelude::*;
+
+use super::{
+    check_output_length, normalize_name, verify_encoded, CryptoError, CryptoResult, EncodingKind,
+};
+
+macro_rules! impl_hmac_algo {
+    ($($algo:ident: $Type:ty),*) => {
+        // NOTE: We use `SimpleHmac` instead of `Hmac` since the blake2
+        // hashers do not implement the block-level traits `Hmac` requires
+        #[derive(Clone)]
+        pub enum HmacAlgo {
+            $(
+                $algo(Box<SimpleHmac<$Type>>),
+            )*
+        }
+
+        impl HmacAlgo {
+            paste::item! {
+                pub const NAMES: &'static [&'static str] = &[
+                    $(
+                        stringify!([<$algo:snake:lower>]),
+                    )*
+                ];
+
+                pub fn new(name: impl AsRef<str>, key: impl AsRef<[u8]>) -> CryptoResult<Self> {
+                    let name = normalize_name(name);
+                    $(
+                        if name == stringify!([<$algo:snake:lower>]) {
+                            let mac = SimpleHmac::<$Type>::new_from_slice(key.as_ref())
+                                .map_err(|_| CryptoError::InvalidKeyLength)?;
+                            return Ok(Self::$algo(Box::new(mac)));
+                        }
+                    )*
+                    Err(Self::invalid_algorithm("hmac", name))
+                }
+
+                /**
+                    Derives a key of the given length using PBKDF2,
+                    with HMAC of the named hash function as its PRF.
+                */
+                pub fn pbkdf2(
+                    name: impl AsRef<str>,
+                    password: impl AsRef<[u8]>,
+                    salt: impl AsRef<[u8]>,
+                    rounds: u32,
+                    length: usize,
+                ) -> CryptoResult<Vec<u8>> {
+                    let name = normalize_name(name);
+                    let mut derived = vec![0; check_output_length("pbkdf2 output", length)?];
+                    $(
+                        if name == stringify!([<$algo:snake:lower>]) {
+                            pbkdf2::pbkdf2::<SimpleHmac<$Type>>(
+                                password.as_ref(),
+                                salt.as_ref(),
+                                rounds,
+                                &mut derived,
+                            )
+                            .map_err(|_| CryptoError::InvalidKeyLength)?;
+                            return Ok(derived);
+                        }
+                    )*
+                    Err(Self::invalid_algorithm("pbkdf2", name))
+                }
+
+                /**
+                    Derives a key of the given length using HKDF,
+                    with HMAC of the named hash function as its PRF.
+                */
+                pub fn hkdf(
+                    name: impl AsRef<str>,
+                    ikm: impl AsRef<[u8]>,
+                    salt: Option<impl AsRef<[u8]>>,
+                    info: Option<impl AsRef<[u8]>>,
+                    length: usize,
+                ) -> CryptoResult<Vec<u8>> {
+                    let name = normalize_name(name);
+                    let salt = salt.as_ref().map(AsRef::as_ref);
+                    let info = info.as_ref().map(AsRef::as_ref).unwrap_or_default();
+                    let mut derived = vec![0; check_output_length("hkdf output", length)?];
+                    $(
+                        if name == stringify!([<$algo:snake:lower>]) {
+                            hkdf::SimpleHkdf::<$Type>::new(salt, ikm.as_ref())
+                                .expand(info, &mut derived)
+                                .map_err(|_| CryptoError::InvalidParams(format!(
+                                    "hkdf output length can be at most 255 times the digest size, got {length} bytes"
+                                )))?;
+                            return Ok(derived);
+                        }
+                    )*
+                    Err(Self::invalid_algorithm("hkdf", name))
+                }
+            }
+
+            fn invalid_algorithm(kind: &'static str, name: String) -> CryptoError {
+                CryptoError::InvalidAlgorithm {
+                    kind,
+                    name,
+                    valid: Self::NAMES,
+                }
+            }
+
+            pub fn update(&mut self, data: impl AsRef<[u8]>) {
+                match self {
+                    $(
+                        Self::$algo(mac) => mac.update(data.as_ref()),
+                    )*
+                }
+            }
+
+            pub fn finalize(&self) -> Vec<u8> {
+                match self {
+                    $(
+                        Self::$algo(mac) => mac.clone().finalize().into_bytes().to_vec(),
+                    )*
+                }
+            }
+
+            pub fn verify(&self, tag: impl AsRef<[u8]>) -> bool {
+                // NOTE: `verify_slice` compares in constant time, which is
+                // the entire point of having this method in the first place
+                match self {
+                    $(
+                        Self::$algo(mac) => mac.clone().verify_slice(tag.as_ref()).is_ok(),
+                    )*
+                }
+            }
+        }
+    }
+}
+
+// Should contain the same fixed output algorithms as the `impl_hash_algo` macro call
+impl_hmac_algo! {
+    Sha1: sha1::Sha1,
+    Sha224: sha2::Sha224,
+    Sha256: sha2::Sha256,
+    Sha384: sha2::Sha384,
+    Sha512: sha2::Sha512,
+    Md5: md5::Md5,
+    Blake2s256: blake2::Blake2s256,
+    Blake2b512: blake2::Blake2b512,
+    Sha3_224: sha3::Sha3_224,
+    Sha3_256: sha3::Sha3_256,
+    Sha3_384: sha3::Sha3_384,
+    Sha3_512: sha3::Sha3_512
+}
+
+#[derive(Clone)]
+pub struct Hmac {
+    algo: Arc<Mutex<HmacAlgo>>,
+}
+
+impl Hmac {
+    pub fn new(
+        name: impl AsRef<str>,
+        key: impl AsRef<[u8]>,
+        content: Option<impl AsRef<[u8]>>,
+    ) -> CryptoResult<Self> {
+        let mut algo = HmacAlgo::new(name, key)?;
+        if let Some(content) = content {
+            algo.update(content);
+        }
+
+        Ok(Self {
+            algo: Arc::new(Mutex::new(algo)),
+        })
+    }
+
+    fn update(&self, content: impl AsRef<[u8]>) -> CryptoResult<&Self> {
+        self.algo
+            .lock()
+            .map_err(|_| CryptoError::Poisoned)?
+            .update(content);
+
+        Ok(self)
+    }
+
+    fn digest(&self, encoding: EncodingKind) -> CryptoResult<Vec<u8>> {
+        let computed = self
+            .algo
+            .lock()
+            .map_err(|_| CryptoError::Poisoned)?
+            .finalize();
+
+        encoding.encode(computed)
+    }
+
+    fn verify(&self, expected: impl AsRef<[u8]>, encoding: EncodingKind) -> CryptoResult<bool> {
+        verify_encoded(expected, encoding, |tag| {
+            Ok(self
+                .algo
+                .lock()
+                .map_err(|_| CryptoError::Poisoned)?
+                .verify(tag))
+        })
+    }
+}
+
+impl LuaUserData for Hmac {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("digest", |lua, this, encoding| {
+            lua.create_string(this.digest(encoding)?)
+        });
+
+        methods.add_method("update", |_, this, content: LuaString| {
+            Ok(this.update(content)?.clone())
+        });
+
+        methods.add_method(
+            "verify",
+            |_, this, (expected, encoding): (LuaString, EncodingKind)| {
+                Ok(this.verify(expected, encoding)?)
+            },
+        );
+    }
+}
@@ -0,0 +1,641 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use base64::{engine::general_purpose as Base64, Engine as _};
+use digest::Digest as _;
+use mlua::prelude::*;
+use tokio::{fs::File, io::AsyncReadExt as _, task};
+
+use crate::lune::{scheduler::Scheduler, util::TableBuilder};
+
+mod checksum;
+mod cipher;
+mod error;
+mod kdf;
+mod keypair;
+mod mac;
+mod random;
+
+pub use checksum::Checksum;
+pub use cipher::CipherKind;
+pub use error::{CryptoError, CryptoResult};
+pub use kdf::KdfOptions;
+pub use keypair::{KeyAlgorithm, KeyFormat, KeyPair};
+pub use mac::{Hmac, HmacAlgo};
+
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/**
+    The largest amount of bytes that may be generated or derived at once.
+*/
+const MAX_OUTPUT_LENGTH: usize = 64 * 1024 * 1024;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable<'static>> {
+    TableBuilder::new(lua)?
+        .with_function("sha1", |_, content: Option<LuaString>| {
+            Ok(Crypto::new("sha1", content)?)
+        })?
+        .with_function("sha256", |_, content: Option<LuaString>| {
+            Ok(Crypto::new("sha256", content)?)
+        })?
+        .with_function("sha512", |_, content: Option<LuaString>| {
+            Ok(Crypto::new("sha512", content)?)
+        })?
+        .with_function("md5", |_, content: Option<LuaString>| {
+            Ok(Crypto::new("md5", content)?)
+        })?
+        .with_function("blake2s256", |_, content: Option<LuaString>| {
+            Ok(Crypto::new("blake2s256", content)?)
+        })?
+        .with_function("blake2b512", |_, content: Option<LuaString>| {
+            Ok(Crypto::new("blake2b512", content)?)
+        })?
+        .with_function(
+            "sha3",
+            |_, (variant, content): (String, Option<LuaString>)| {
+                let crypto = match variant.as_str() {
+                    "224" | "256" | "384" | "512" => {
+                        Crypto::new(format!("sha3_{variant}"), content)
+                    }
+
+                    &_ => Err(CryptoError::InvalidAlgorithm {
+                        kind: "sha3",
+                        name: variant,
+                        valid: &["224", "256", "384", "512"],
+                    }),
+                };
+                Ok(crypto?)
+            },
+        )?
+        .with_function("hash", |_, (algo, content): (String, Option<LuaString>)| {
+            Ok(Crypto::new(algo, content)?)
+        })?
+        .with_async_function("hashFile", crypto_hash_file)?
+        .with_function(
+            "hmac",
+            |_, (algo, key, content): (String, LuaString, Option<LuaString>)| {
+                Ok(Hmac::new(algo, key, content)?)
+            },
+        )?
+        .with_function("randomBytes", crypto_random_bytes)?
+        .with_function("uuid", |_, version: Option<u8>| {
+            Ok(random::uuid(version.unwrap_or(4))?)
+        })?
+        .with_function("encrypt", crypto_encrypt)?
+        .with_function("decrypt", crypto_decrypt)?
+        .with_function("hkdf", crypto_hkdf)?
+        .with_function("generateKeyPair", |_, algorithm: KeyAlgorithm| {
+            Ok(KeyPair::generate(algorithm))
+        })?
+        .with_function(
+            "importPrivateKey",
+            |_, (algorithm, data, format): (KeyAlgorithm, LuaString, Option<KeyFormat>)| {
+                Ok(KeyPair::import_private_key(
+                    algorithm,
+                    data,
+                    format.unwrap_or_default(),
+                )?)
+            },
+        )?
+        .with_function(
+            "importPublicKey",
+            |_, (algorithm, data, format): (KeyAlgorithm, LuaString, Option<KeyFormat>)| {
+                Ok(KeyPair::import_public_key(
+                    algorithm,
+                    data,
+                    format.unwrap_or_default(),
+                )?)
+            },
+        )?
+        .with_async_function("pbkdf2", crypto_pbkdf2)?
+        .with_async_function("argon2id", crypto_argon2id)?
+        .with_async_function("scrypt", crypto_scrypt)?
+        .with_async_function("hashPassword", crypto_hash_password)?
+        .with_async_function("verifyPassword", crypto_verify_password)?
+        .build_readonly()
+}
+
+async fn crypto_hash_file<'lua>(
+    lua: &'lua Lua,
+    (algo, path, encoding): (String, String, Option<EncodingKind>),
+) -> LuaResult<LuaString<'lua>> {
+    let algo = CryptoAlgo::new(algo)?;
+
+    // NOTE: Files may be very large, so we read and hash them in chunks on
+    // a background task instead of loading them into a lua string at once
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    let mut algo = sched.spawn(hash_file(algo, path)).await.map_err(|_| {
+        LuaError::RuntimeError("File hashing was cancelled before it finished".to_string())
+    })??;
+
+    // Checksums are most commonly compared as hex strings, so unlike
+    // the other functions here we default to hex instead of binary
+    lua.create_string(algo.digest(encoding.unwrap_or(EncodingKind::Hex), None)?)
+}
+
+async fn hash_file(mut algo: CryptoAlgo, path: String) -> LuaResult<CryptoAlgo> {
+    let mut file = File::open(&path).await.into_lua_err()?;
+    let mut buffer = vec![0; FILE_CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer).await.into_lua_err()?;
+        if read == 0 {
+            break;
+        }
+        algo.update(&buffer[..read]);
+    }
+    Ok(algo)
+}
+
+fn crypto_random_bytes<'lua>(
+    lua: &'lua Lua,
+    (length, encoding): (usize, Option<EncodingKind>),
+) -> LuaResult<LuaString<'lua>> {
+    let bytes = random::random_bytes(length)?;
+    lua.create_string(encoding.unwrap_or(EncodingKind::Binary).encode(bytes)?)
+}
+
+fn crypto_encrypt<'lua>(
+    lua: &'lua Lua,
+    (cipher, key, nonce, plaintext, aad, encoding): (
+        CipherKind,
+        LuaString<'lua>,
+        LuaString<'lua>,
+        LuaString<'lua>,
+        Option<LuaString<'lua>>,
+        Option<EncodingKind>,
+    ),
+) -> LuaResult<LuaString<'lua>> {
+    let sealed = cipher.encrypt(key, nonce, plaintext, aad)?;
+    lua.create_string(encoding.unwrap_or(EncodingKind::Binary).encode(sealed)?)
+}
+
+fn crypto_decrypt<'lua>(
+    lua: &'lua Lua,
+    (cipher, key, nonce, ciphertext, aad, encoding): (
+        CipherKind,
+        LuaString<'lua>,
+        LuaString<'lua>,
+        LuaString<'lua>,
+        Option<LuaString<'lua>>,
+        Option<EncodingKind>,
+    ),
+) -> LuaResult<LuaString<'lua>> {
+    let ciphertext = encoding
+        .unwrap_or(EncodingKind::Binary)
+        .decode(ciphertext)?;
+    lua.create_string(cipher.decrypt(key, nonce, ciphertext, aad)?)
+}
+
+fn crypto_hkdf<'lua>(
+    lua: &'lua Lua,
+    (algo, ikm, length, salt, info, encoding): (
+        String,
+        LuaString<'lua>,
+        usize,
+        Option<LuaString<'lua>>,
+        Option<LuaString<'lua>>,
+        Option<EncodingKind>,
+    ),
+) -> LuaResult<LuaString<'lua>> {
+    let derived = HmacAlgo::hkdf(algo, ikm, salt, info, length)?;
+    lua.create_string(encoding.unwrap_or(EncodingKind::Binary).encode(derived)?)
+}
+
+// NOTE: The functions below are intentionally slow to compute, so we run
+// them on a blocking thread to not stall the scheduler while they run
+
+async fn crypto_pbkdf2<'lua>(
+    lua: &'lua Lua,
+    (algo, password, salt, iterations, length, encoding): (
+        String,
+        LuaString<'lua>,
+        LuaString<'lua>,
+        u32,
+        usize,
+        Option<EncodingKind>,
+    ),
+) -> LuaResult<LuaString<'lua>> {
+    let password = password.as_bytes().to_vec();
+    let salt = salt.as_bytes().to_vec();
+    let derived =
+        task::spawn_blocking(move || HmacAlgo::pbkdf2(algo, password, salt, iterations, length))
+            .await
+            .into_lua_err()??;
+    lua.create_string(encoding.unwrap_or(EncodingKind::Binary).encode(derived)?)
+}
+
+async fn crypto_argon2id<'lua>(
+    lua: &'lua Lua,
+    (password, salt, options, encoding): (
+        LuaString<'lua>,
+        LuaString<'lua>,
+        KdfOptions,
+        Option<EncodingKind>,
+    ),
+) -> LuaResult<LuaString<'lua>> {
+    let password = password.as_bytes().to_vec();
+    let salt = salt.as_bytes().to_vec();
+    let derived = task::spawn_blocking(move || kdf::argon2id(password, salt, &options))
+        .await
+        .into_lua_err()??;
+    lua.create_string(encoding.unwrap_or(EncodingKind::Binary).encode(derived)?)
+}
+
+async fn crypto_scrypt<'lua>(
+    lua: &'lua Lua,
+    (password, salt, options, encoding): (
+        LuaString<'lua>,
+        LuaString<'lua>,
+        KdfOptions,
+        Option<EncodingKind>,
+    ),
+) -> LuaResult<LuaString<'lua>> {
+    let password = password.as_bytes().to_vec();
+    let salt = salt.as_bytes().to_vec();
+    let derived = task::spawn_blocking(move || kdf::scrypt(password, salt, &options))
+        .await
+        .into_lua_err()??;
+    lua.create_string(encoding.unwrap_or(EncodingKind::Binary).encode(derived)?)
+}
+
+async fn crypto_hash_password<'lua>(
+    _: &'lua Lua,
+    (password, options): (LuaString<'lua>, KdfOptions),
+) -> LuaResult<String> {
+    let password = password.as_bytes().to_vec();
+    let hash = task::spawn_blocking(move || kdf::hash_password(password, &options))
+        .await
+        .into_lua_err()??;
+    Ok(hash)
+}
+
+async fn crypto_verify_password<'lua>(
+    _: &'lua Lua,
+    (password, hash): (LuaString<'lua>, String),
+) -> LuaResult<bool> {
+    let password = password.as_bytes().to_vec();
+    let verified = task::spawn_blocking(move || kdf::verify_password(password, hash))
+        .await
+        .into_lua_err()??;
+    Ok(verified)
+}
+
+macro_rules! impl_hash_algo {
+    (
+        fixed { $($algo:ident: $Type:ty),* $(,)? }
+        extendable { $($xof_algo:ident: $XofType:ty => $xof_length:expr),* $(,)? }
+        checksum { $($sum_algo:ident: $SumType:ty),* $(,)? }
+    ) => {
+        #[derive(Clone)]
+        pub enum CryptoAlgo {
+            $(
+                $algo(Box<$Type>),
+            )*
+            $(
+                $xof_algo(Box<$XofType>),
+            )*
+            $(
+                $sum_algo(Box<$SumType>),
+            )*
+        }
+
+        impl CryptoAlgo {
+            paste::item! {
+                pub const NAMES: &'static [&'static str] = &[
+                    $(
+                        stringify!([<$algo:snake:lower>]),
+                    )*
+                    $(
+                        stringify!([<$xof_algo:snake:lower>]),
+                    )*
+                    $(
+                        stringify!([<$sum_algo:snake:lower>]),
+                    )*
+                ];
+
+                pub fn new(name: impl AsRef<str>) -> CryptoResult<Self> {
+                    let name = normalize_name(name);
+                    $(
+                        if name == stringify!([<$algo:snake:lower>]) {
+                            return Ok(Self::$algo(Box::default()));
+                        }
+                    )*
+                    $(
+                        if name == stringify!([<$xof_algo:snake:lower>]) {
+                            return Ok(Self::$xof_algo(Box::default()));
+                        }
+                    )*
+                    $(
+                        if name == stringify!([<$sum_algo:snake:lower>]) {
+                            return Ok(Self::$sum_algo(Box::default()));
+                        }
+                    )*
+                    Err(CryptoError::InvalidAlgorithm {
+                        kind: "hash",
+                        name,
+                        valid: Self::NAMES,
+                    })
+                }
+            }
+
+            pub fn update(&mut self, data: impl AsRef<[u8]>) {
+                match self {
+                    $(
+                        Self::$algo(hasher) => hasher.update(data),
+                    )*
+                    $(
+                        Self::$xof_algo(hasher) => digest::Update::update(hasher.as_mut(), data.as_ref()),
+                    )*
+                    $(
+                        Self::$sum_algo(hasher) => Checksum::update(hasher.as_mut(), data.as_ref()),
+                    )*
+                }
+            }
+
+            /**
+                Computes the digest of all data given so far.
+
+                Computing a digest never resets or consumes the state, for any of the
+                algorithms, so more data may be given after getting a digest and
+                the next digest will then be of all data given up until that point.
+
+                Extendable output functions (SHAKE and BLAKE3) may output
+                any length, other algorithms only accept their own length.
+            */
+            pub fn digest(&mut self, encoding: EncodingKind, length: Option<usize>) -> CryptoResult<Vec<u8>> {
+                let computed = match self {
+                    $(
+                        Self::$algo(hasher) => hasher.as_ref().clone().finalize().to_vec(),
+                    )*
+                    $(
+                        Self::$xof_algo(hasher) => {
+                            let length = check_output_length("digest", length.unwrap_or($xof_length))?;
+                            let mut computed = vec![0; length];
+                            digest::ExtendableOutput::finalize_xof_into(
+                                hasher.as_ref().clone(),
+                                &mut computed,
+                            );
+                            computed
+                        }
+                    )*
+                    $(
+                        Self::$sum_algo(hasher) => Checksum::finalize(hasher.as_ref()),
+                    )*
+                };
+
+                match length {
+                    Some(length) if length != computed.len() => Err(CryptoError::InvalidLength {
+                        kind: "digest",
+                        expected: computed.len(),
+                        got: length,
+                    }),
+                    _ => encoding.encode(computed),
+                }
+            }
+        }
+    }
+}
+
+// Macro call creates the CryptoAlgo enum and implementations for it, the
+// cryptographic algorithms should also be added to the `impl_hmac_algo` call
+impl_hash_algo! {
+    fixed {
+        Sha1: sha1::Sha1,
+        Sha224: sha2::Sha224,
+        Sha256: sha2::Sha256,
+        Sha384: sha2::Sha384,
+        Sha512: sha2::Sha512,
+        Md5: md5::Md5,
+        Blake2s256: blake2::Blake2s256,
+        Blake2b512: blake2::Blake2b512,
+        Sha3_224: sha3::Sha3_224,
+        Sha3_256: sha3::Sha3_256,
+        Sha3_384: sha3::Sha3_384,
+        Sha3_512: sha3::Sha3_512,
+    }
+    extendable {
+        Shake128: sha3::Shake128 => 32,
+        Shake256: sha3::Shake256 => 64,
+        Blake3: blake3::Hasher => 32,
+    }
+    checksum {
+        Crc32: checksum::Crc32,
+        Xxh64: checksum::Xxh64,
+        Xxh3_64: checksum::Xxh3_64,
+        Xxh3_128: checksum::Xxh3_128,
+    }
+}
+
+fn normalize_name(name: impl AsRef<str>) -> String {
+    name.as_ref().trim().to_ascii_lowercase().replace('-', "_")
+}
+
+/**
+    Checks that an output length given by the user is small enough to be
+    allocated, since a failed allocation aborts instead of throwing an error.
+*/
+fn check_output_length(kind: &'static str, length: usize) -> CryptoResult<usize> {
+    if length > MAX_OUTPUT_LENGTH {
+        Err(CryptoError::LengthTooLarge {
+            kind,
+            max: MAX_OUTPUT_LENGTH,
+            got: length,
+        })
+    } else {
+        Ok(length)
+    }
+}
+
+/**
+    Decodes a signature or tag using the given encoding, then verifies it.
+
+    A signature that can't be decoded can never be valid, so we
+    treat it the same as a mismatch instead of throwing an error.
+*/
+fn verify_encoded(
+    signature: impl AsRef<[u8]>,
+    encoding: EncodingKind,
+    verify: impl FnOnce(Vec<u8>) -> CryptoResult<bool>,
+) -> CryptoResult<bool> {
+    match encoding.decode(signature) {
+        Ok(signature) => verify(signature),
+        Err(_) => Ok(false),
+    }
+}
+
+/**
+    Converts a lua string into one of the given algorithms, using the
+    position of its name in `names` to pick the matching algorithm.
+
+    Names are normalized the same way as hash algorithm names, and any
+    separators are ignored, so `"AES-256-GCM"` matches `"aes256gcm"`.
+*/
+fn algorithm_from_lua<T: Copy>(
+    value: LuaValue,
+    to: &'static str,
+    kind: &'static str,
+    names: &'static [&'static str],
+    algorithms: &[T],
+) -> LuaResult<T> {
+    let LuaValue::String(s) = &value else {
+        return Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to,
+            message: None,
+        });
+    };
+    let name = normalize_name(s.to_string_lossy()).replace('_', "");
+    match names.iter().position(|n| n.replace('_', "") == name) {
+        Some(index) => Ok(algorithms[index]),
+        None => Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to,
+            message: Some(
+                CryptoError::InvalidAlgorithm {
+                    kind,
+                    name,
+                    valid: names,
+                }
+                .to_string(),
+            ),
+        }),
+    }
+}
+
+#[derive(Clone)]
+pub struct Crypto {
+    algo: Arc<Mutex<CryptoAlgo>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq)]
+pub enum EncodingKind {
+    Utf8,
+    Base64,
+    Hex,
+    Binary,
+}
+
+impl EncodingKind {
+    pub const NAMES: &'static [&'static str] = &["utf8", "base64", "hex", "binary"];
+
+    pub fn encode(&self, bytes: Vec<u8>) -> CryptoResult<Vec<u8>> {
+        match self {
+            Self::Utf8 => String::from_utf8(bytes)
+                .map(String::into_bytes)
+                .map_err(|_| CryptoError::InvalidUtf8),
+            Self::Base64 => Ok(Base64::STANDARD.encode(bytes).into_bytes()),
+            Self::Hex => Ok(hex::encode(bytes).into_bytes()),
+            Self::Binary => Ok(bytes),
+        }
+    }
+
+    pub fn decode(&self, encoded: impl AsRef<[u8]>) -> CryptoResult<Vec<u8>> {
+        match self {
+            Self::Utf8 | Self::Binary => Ok(encoded.as_ref().to_vec()),
+            Self::Base64 => Ok(Base64::STANDARD.decode(encoded)?),
+            Self::Hex => Ok(hex::decode(encoded)?),
+        }
+    }
+}
+
+impl TryFrom<usize> for EncodingKind {
+    type Error = CryptoError;
+
+    fn try_from(value: usize) -> CryptoResult<Self> {
+        match value {
+            0 => Ok(Self::Utf8),
+            1 => Ok(Self::Base64),
+            2 => Ok(Self::Hex),
+            3 => Ok(Self::Binary),
+            _ => Err(CryptoError::InvalidEncoding(value.to_string())),
+        }
+    }
+}
+
+impl FromStr for EncodingKind {
+    type Err = CryptoError;
+
+    fn from_str(value: &str) -> CryptoResult<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "utf8" => Ok(Self::Utf8),
+            "base64" => Ok(Self::Base64),
+            "hex" => Ok(Self::Hex),
+            "binary" | "raw" => Ok(Self::Binary),
+            _ => Err(CryptoError::InvalidEncoding(value.to_string())),
+        }
+    }
+}
+
+impl FromLua<'_> for EncodingKind {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        let parsed = match &value {
+            LuaValue::Integer(int) => usize::try_from(*int)
+                .map_err(|_| CryptoError::InvalidEncoding(int.to_string()))
+                .and_then(EncodingKind::try_from),
+            LuaValue::Number(num) if num.fract() == 0.0 && *num >= 0.0 => {
+                EncodingKind::try_from(*num as usize)
+            }
+            LuaValue::Number(num) => Err(CryptoError::InvalidEncoding(num.to_string())),
+            LuaValue::String(str) => str.to_string_lossy().parse(),
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "EncodingKind",
+                    message: Some("value must be a an Integer, Number or String".to_string()),
+                })
+            }
+        };
+        parsed.map_err(|e| LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "EncodingKind",
+            message: Some(e.to_string()),
+        })
+    }
+}
+
+impl Crypto {
+    pub fn new(name: impl AsRef<str>, content: Option<impl AsRef<[u8]>>) -> CryptoResult<Self> {
+        let mut algo = CryptoAlgo::new(name)?;
+        if let Some(content) = content {
+            algo.update(content);
+        }
+
+        Ok(Self {
+            algo: Arc::new(Mutex::new(algo)),
+        })
+    }
+
+    fn update(&self, content: impl AsRef<[u8]>) -> CryptoResult<&Self> {
+        self.algo
+            .lock()
+            .map_err(|_| CryptoError::Poisoned)?
+            .update(content);
+
+        Ok(self)
+    }
+
+    fn digest(&self, encoding: EncodingKind, length: Option<usize>) -> CryptoResult<Vec<u8>> {
+        self.algo
+            .lock()
+            .map_err(|_| CryptoError::Poisoned)?
+            .digest(encoding, length)
+    }
+}
+
+impl LuaUserData for Crypto {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method(
+            "digest",
+            |lua, this, (encoding, length): (EncodingKind, Option<usize>)| {
+                lua.create_string(this.digest(encoding, length)?)
+            },
+        );
+
+        methods.add_method("update", |_, this, content: LuaString| {
+            Ok(this.update(content)?.clone())
+        });
+    }
+}
@@ -0,0 +1,38 @@
+use rand_core::{OsRng, RngCore as _};
+use uuid::Uuid;
+
+use super::{check_output_length, CryptoError, CryptoResult};
+
+/**
+    Generates the given amount of random bytes using the
+    cryptographically secure random number generator of the OS.
+*/
+pub fn random_bytes(length: usize) -> CryptoResult<Vec<u8>> {
+    let mut bytes = vec![0; check_output_length("random bytes", length)?];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .map_err(|e| CryptoError::Random(e.to_string()))?;
+    Ok(bytes)
+}
+
+/**
+    Generates a new random UUID of the given version, formatted
+    as a lowercase, hyphenated string.
+
+    Version 7 UUIDs are prefixed with the current unix timestamp
+    and will sort in the order they were generated in.
+*/
+pub fn uuid(version: u8) -> CryptoResult<String> {
+    let uuid = match version {
+        4 => Uuid::new_v4(),
+        7 => Uuid::now_v7(),
+        _ => {
+            return Err(CryptoError::InvalidAlgorithm {
+                kind: "uuid",
+                name: version.to_string(),
+                valid: &["4", "7"],
+            })
+        }
+    };
+    Ok(uuid.hyphenated().to_string())
+}
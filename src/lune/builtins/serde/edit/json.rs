@@ -0,0 +1,178 @@
+use jsonc_parser::{
+    cst::{CstInputValue, CstNode, CstRootNode},
+    ParseOptions,
+};
+use mlua::prelude::*;
+use serde_json::Value as JsonValue;
+
+use super::{
+    super::encode_decode::{LUA_DESERIALIZE_OPTIONS, LUA_SERIALIZE_OPTIONS},
+    path::{EditPath, EditPathSegment},
+};
+
+pub fn parse(text: &str) -> LuaResult<CstRootNode> {
+    CstRootNode::parse(text, &ParseOptions::default())
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid json data - {e}")))
+}
+
+pub fn get<'lua>(lua: &'lua Lua, root: &CstRootNode, path: &EditPath) -> LuaResult<LuaValue<'lua>> {
+    match find(root, path.segments()) {
+        Some(node) => node_to_lua(lua, &node),
+        None => Ok(LuaValue::Nil),
+    }
+}
+
+pub fn set(lua: &Lua, root: &CstRootNode, path: &EditPath, value: LuaValue) -> LuaResult<()> {
+    if value.is_nil() {
+        return remove(lua, root, path).map(|_| ());
+    }
+    let serialized: JsonValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+    let value = json_to_input(serialized);
+
+    let (parents, last) = path.split_last()?;
+    let mut node = match root.value() {
+        Some(node) => node,
+        None => {
+            root.set_value(empty_container_for(&path.segments()[0]));
+            root.value().expect("root value was just set")
+        }
+    };
+    for (index, segment) in parents.iter().enumerate() {
+        node = match child(&node, segment) {
+            Some(child) => child,
+            None => {
+                let next = &path.segments()[index + 1];
+                insert(&node, segment, empty_container_for(next), path)?
+            }
+        };
+    }
+
+    match last {
+        EditPathSegment::Key(key) => {
+            let object = node
+                .as_object()
+                .ok_or_else(|| path.error("set", "the parent value is not an object"))?;
+            match object.get(key) {
+                Some(prop) => prop.set_value(value),
+                None => {
+                    object.append(key, value);
+                }
+            }
+        }
+        EditPathSegment::Index(index) => {
+            let array = node
+                .as_array()
+                .ok_or_else(|| path.error("set", "the parent value is not an array"))?;
+            let elements = array.elements();
+            if let Some(existing) = elements.get(*index) {
+                array.insert(*index, value);
+                existing.clone().remove();
+            } else {
+                insert(&node, last, value, path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn remove<'lua>(
+    lua: &'lua Lua,
+    root: &CstRootNode,
+    path: &EditPath,
+) -> LuaResult<LuaValue<'lua>> {
+    let (parents, last) = path.split_last()?;
+    let Some(node) = find(root, parents) else {
+        return Ok(LuaValue::Nil);
+    };
+    match last {
+        EditPathSegment::Key(key) => match node.as_object().and_then(|o| o.get(key)) {
+            Some(prop) => {
+                let removed = match prop.value() {
+                    Some(value) => node_to_lua(lua, &value)?,
+                    None => LuaValue::Nil,
+                };
+                prop.remove();
+                Ok(removed)
+            }
+            None => Ok(LuaValue::Nil),
+        },
+        EditPathSegment::Index(_) => match child(&node, last) {
+            Some(element) => {
+                let removed = node_to_lua(lua, &element)?;
+                element.remove();
+                Ok(removed)
+            }
+            None => Ok(LuaValue::Nil),
+        },
+    }
+}
+
+fn find(root: &CstRootNode, segments: &[EditPathSegment]) -> Option<CstNode> {
+    let mut node = root.value()?;
+    for segment in segments {
+        node = child(&node, segment)?;
+    }
+    Some(node)
+}
+
+fn child(node: &CstNode, segment: &EditPathSegment) -> Option<CstNode> {
+    match segment {
+        EditPathSegment::Key(key) => node.as_object()?.get(key)?.value(),
+        EditPathSegment::Index(index) => node.as_array()?.elements().get(*index).cloned(),
+    }
+}
+
+fn insert(
+    node: &CstNode,
+    segment: &EditPathSegment,
+    value: CstInputValue,
+    path: &EditPath,
+) -> LuaResult<CstNode> {
+    let inserted = match segment {
+        EditPathSegment::Key(key) => node
+            .as_object()
+            .ok_or_else(|| path.error("set", "a parent value is not an object"))?
+            .append(key, value)
+            .value(),
+        EditPathSegment::Index(index) => {
+            let array = node
+                .as_array()
+                .ok_or_else(|| path.error("set", "a parent value is not an array"))?;
+            let len = array.elements().len();
+            if *index != len {
+                return Err(path.out_of_range_error("set", *index, len));
+            }
+            Some(array.append(value))
+        }
+    };
+    Ok(inserted.expect("inserted values always exist"))
+}
+
+fn empty_container_for(segment: &EditPathSegment) -> CstInputValue {
+    match segment {
+        EditPathSegment::Key(_) => CstInputValue::Object(Vec::new()),
+        EditPathSegment::Index(_) => CstInputValue::Array(Vec::new()),
+    }
+}
+
+fn node_to_lua<'lua>(lua: &'lua Lua, node: &CstNode) -> LuaResult<LuaValue<'lua>> {
+    match node.to_serde_value() {
+        Some(value) => lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS),
+        None => Ok(LuaValue::Nil),
+    }
+}
+
+fn json_to_input(value: JsonValue) -> CstInputValue {
+    match value {
+        JsonValue::Null => CstInputValue::Null,
+        JsonValue::Bool(b) => CstInputValue::Bool(b),
+        JsonValue::Number(n) => CstInputValue::Number(n.to_string()),
+        JsonValue::String(s) => CstInputValue::String(s),
+        JsonValue::Array(a) => CstInputValue::Array(a.into_iter().map(json_to_input).collect()),
+        JsonValue::Object(o) => CstInputValue::Object(
+            o.into_iter()
+                .map(|(key, value)| (key, json_to_input(value)))
+                .collect(),
+        ),
+    }
+}
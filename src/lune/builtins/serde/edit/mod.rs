@@ -0,0 +1,138 @@
+use std::fmt;
+
+use jsonc_parser::cst::CstRootNode;
+use mlua::prelude::*;
+use toml_edit::Document as TomlDocument;
+
+mod json;
+mod path;
+mod toml;
+
+pub use path::EditPath;
+
+#[derive(Debug, Clone, Copy)]
+pub enum EditFormat {
+    Json,
+    Toml,
+}
+
+impl<'lua> FromLua<'lua> for EditFormat {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "json" => Ok(Self::Json),
+                "toml" => Ok(Self::Toml),
+                // NOTE: No yaml library we can use edits documents without sometimes
+                // producing broken yaml, which is worse than losing formatting, so
+                // yaml is unsupported and gets a more helpful error than other formats
+                "yaml" | "yml" => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "EditFormat",
+                    message: Some(
+                        "Editing yaml while keeping its formatting is not supported, \
+                        use serde.decode and serde.encode to edit yaml instead"
+                            .to_string(),
+                    ),
+                }),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "EditFormat",
+                    message: Some(format!(
+                        "Invalid format '{kind}', valid formats are:  json, toml"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "EditFormat",
+                message: None,
+            })
+        }
+    }
+}
+
+/**
+    A document that can be edited while keeping its
+    comments, whitespace, and the order of its keys.
+
+    The json format also accepts comments and trailing commas,
+    and keeps them when the document is converted back to a string.
+*/
+pub enum EditableDocument {
+    Json(CstRootNode),
+    Toml(Box<TomlDocument>),
+}
+
+impl EditableDocument {
+    pub fn parse(format: EditFormat, text: &str) -> LuaResult<Self> {
+        match format {
+            EditFormat::Json => json::parse(text).map(Self::Json),
+            EditFormat::Toml => toml::parse(text).map(|doc| Self::Toml(Box::new(doc))),
+        }
+    }
+
+    pub fn format(&self) -> EditFormat {
+        match self {
+            Self::Json(_) => EditFormat::Json,
+            Self::Toml(_) => EditFormat::Toml,
+        }
+    }
+
+    pub fn get<'lua>(&self, lua: &'lua Lua, path: &EditPath) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            Self::Json(root) => json::get(lua, root, path),
+            Self::Toml(doc) => toml::get(lua, doc, path),
+        }
+    }
+
+    pub fn set(&mut self, lua: &Lua, path: &EditPath, value: LuaValue) -> LuaResult<()> {
+        match self {
+            Self::Json(root) => json::set(lua, root, path, value),
+            Self::Toml(doc) => toml::set(lua, doc, path, value),
+        }
+    }
+
+    pub fn remove<'lua>(&mut self, lua: &'lua Lua, path: &EditPath) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            Self::Json(root) => json::remove(lua, root, path),
+            Self::Toml(doc) => toml::remove(lua, doc, path),
+        }
+    }
+}
+
+impl fmt::Display for EditableDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(root) => root.fmt(f),
+            Self::Toml(doc) => doc.fmt(f),
+        }
+    }
+}
+
+impl LuaUserData for EditableDocument {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("format", |_, this| {
+            Ok(match this.format() {
+                EditFormat::Json => "json",
+                EditFormat::Toml => "toml",
+            })
+        });
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("get", |lua, this, path: EditPath| this.get(lua, &path));
+
+        methods.add_method_mut("set", |lua, this, (path, value): (EditPath, LuaValue)| {
+            this.set(lua, &path, value)
+        });
+
+        methods.add_method_mut("remove", |lua, this, path: EditPath| {
+            this.remove(lua, &path)
+        });
+
+        methods.add_method("toString", |_, this, ()| Ok(this.to_string()));
+
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| Ok(this.to_string()));
+    }
+}
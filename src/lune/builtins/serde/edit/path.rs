@@ -0,0 +1,113 @@
+use std::fmt;
+
+use mlua::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditPathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/**
+    A path to a value inside of an editable document.
+
+    May be given from lua as a string of keys separated by dots, such as
+    `"package.version"`, or as an array of keys and indices for keys that
+    contain dots or for indexing into arrays, such as `{ "bin", 1, "name" }`.
+
+    Indices in paths start at 1 to match lua, but are stored starting at 0.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditPath(Vec<EditPathSegment>);
+
+impl EditPath {
+    pub fn segments(&self) -> &[EditPathSegment] {
+        &self.0
+    }
+
+    pub fn error(&self, action: &str, reason: impl fmt::Display) -> LuaError {
+        LuaError::RuntimeError(format!("Failed to {action} '{self}' - {reason}"))
+    }
+
+    pub fn out_of_range_error(&self, action: &str, index: usize, len: usize) -> LuaError {
+        self.error(
+            action,
+            format!(
+                "index {} is out of range for an array of length {len}",
+                index + 1
+            ),
+        )
+    }
+
+    pub fn split_last(&self) -> LuaResult<(&[EditPathSegment], &EditPathSegment)> {
+        match self.0.split_last() {
+            Some((last, parents)) => Ok((parents, last)),
+            None => Err(LuaError::RuntimeError(
+                "Path must contain at least one key or index".to_string(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EditPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, segment) in self.0.iter().enumerate() {
+            match segment {
+                EditPathSegment::Key(key) if index == 0 => write!(f, "{key}")?,
+                EditPathSegment::Key(key) => write!(f, ".{key}")?,
+                EditPathSegment::Index(index) => write!(f, "[{}]", index + 1)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'lua> FromLua<'lua> for EditPath {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::String(s) => {
+                let s = s.to_str()?;
+                if s.is_empty() {
+                    Ok(Self(Vec::new()))
+                } else {
+                    Ok(Self(
+                        s.split('.')
+                            .map(|key| EditPathSegment::Key(key.to_string()))
+                            .collect(),
+                    ))
+                }
+            }
+            LuaValue::Table(tab) => tab
+                .clone()
+                .sequence_values::<LuaValue>()
+                .map(|segment| match segment? {
+                    LuaValue::String(key) => Ok(EditPathSegment::Key(key.to_str()?.to_string())),
+                    LuaValue::Integer(index) if index >= 1 => {
+                        Ok(EditPathSegment::Index(index as usize - 1))
+                    }
+                    LuaValue::Number(index) if index >= 1.0 && index.fract() == 0.0 => {
+                        Ok(EditPathSegment::Index(index as usize - 1))
+                    }
+                    segment => Err(LuaError::FromLuaConversionError {
+                        from: segment.type_name(),
+                        to: "EditPath",
+                        message: Some(
+                            "Invalid path - expected only string keys \
+                            and whole number indices starting at 1"
+                                .to_string(),
+                        ),
+                    }),
+                })
+                .collect::<LuaResult<_>>()
+                .map(Self),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "EditPath",
+                message: Some(format!(
+                    "Invalid path - expected string or table, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use mlua::prelude::*;
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+use toml::Value as TomlValue;
+use toml_edit::{ser::ValueSerializer, Array, Document, InlineTable, Item, Value};
+
+use super::{
+    super::encode_decode::{LUA_DESERIALIZE_OPTIONS, LUA_SERIALIZE_OPTIONS},
+    path::{EditPath, EditPathSegment},
+};
+
+pub fn parse(text: &str) -> LuaResult<Document> {
+    text.parse::<Document>()
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid toml data - {e}")))
+}
+
+pub fn get<'lua>(lua: &'lua Lua, doc: &Document, path: &EditPath) -> LuaResult<LuaValue<'lua>> {
+    let mut item = doc.as_item();
+    for segment in path.segments() {
+        item = match child(item, segment) {
+            Some(child) => child,
+            None => return Ok(LuaValue::Nil),
+        };
+    }
+    item_to_lua(lua, item.clone())
+}
+
+pub fn set(lua: &Lua, doc: &mut Document, path: &EditPath, value: LuaValue) -> LuaResult<()> {
+    // NOTE: TOML has no null value, so setting nil is the same as removing
+    if value.is_nil() {
+        return remove(lua, doc, path).map(|_| ());
+    }
+    let serialized: TomlValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+    let value = serialized
+        .serialize(ValueSerializer::new())
+        .into_lua_err()?;
+
+    let (parents, last) = path.split_last()?;
+    let mut item = doc.as_item_mut();
+    for (index, segment) in parents.iter().enumerate() {
+        if child(item, segment).is_none() {
+            let container = match path.segments()[index + 1] {
+                EditPathSegment::Key(_) => Value::InlineTable(InlineTable::new()),
+                EditPathSegment::Index(_) => Value::Array(Array::new()),
+            };
+            let intermediate = new_item(item.is_table(), container);
+            match (segment, item.as_table_like_mut()) {
+                (EditPathSegment::Key(key), Some(table)) => {
+                    table.insert(key, intermediate);
+                }
+                _ => return Err(path.error("set", "a parent value is missing or not a table")),
+            }
+        }
+        item = child_mut(item, segment)
+            .ok_or_else(|| path.error("set", "a parent value is missing or not a table"))?;
+    }
+
+    let is_table = item.is_table();
+    match last {
+        EditPathSegment::Key(key) => {
+            let table = item
+                .as_table_like_mut()
+                .ok_or_else(|| path.error("set", "the parent value is not a table"))?;
+            match table.get_mut(key) {
+                Some(existing) => replace_item(existing, value),
+                None => {
+                    table.insert(key, new_item(is_table, value));
+                }
+            }
+        }
+        EditPathSegment::Index(index) => {
+            if let Some(array) = item.as_array_mut() {
+                if *index < array.len() {
+                    let mut value = value;
+                    let existing = array.get_mut(*index).expect("index was checked");
+                    *value.decor_mut() = existing.decor().clone();
+                    *existing = value;
+                } else if *index == array.len() {
+                    array.push(value);
+                } else {
+                    return Err(path.out_of_range_error("set", *index, array.len()));
+                }
+            } else if let Some(array) = item.as_array_of_tables_mut() {
+                let table = match value {
+                    Value::InlineTable(table) => table.into_table(),
+                    _ => return Err(path.error("set", "arrays of tables may only contain tables")),
+                };
+                if *index < array.len() {
+                    *array.get_mut(*index).expect("index was checked") = table;
+                } else if *index == array.len() {
+                    array.push(table);
+                } else {
+                    return Err(path.out_of_range_error("set", *index, array.len()));
+                }
+            } else {
+                return Err(path.error("set", "the parent value is not an array"));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn remove<'lua>(
+    lua: &'lua Lua,
+    doc: &mut Document,
+    path: &EditPath,
+) -> LuaResult<LuaValue<'lua>> {
+    let (parents, last) = path.split_last()?;
+    let mut item = doc.as_item_mut();
+    for segment in parents {
+        item = match child_mut(item, segment) {
+            Some(child) => child,
+            None => return Ok(LuaValue::Nil),
+        };
+    }
+    let removed = match last {
+        EditPathSegment::Key(key) => item.as_table_like_mut().and_then(|table| table.remove(key)),
+        EditPathSegment::Index(index) => {
+            if let Some(array) = item.as_array_mut() {
+                (*index < array.len()).then(|| Item::Value(array.remove(*index)))
+            } else if let Some(array) = item.as_array_of_tables_mut() {
+                let table = array.get(*index).cloned();
+                if table.is_some() {
+                    array.remove(*index);
+                }
+                table.map(Item::Table)
+            } else {
+                None
+            }
+        }
+    };
+    match removed {
+        Some(item) => item_to_lua(lua, item),
+        None => Ok(LuaValue::Nil),
+    }
+}
+
+fn child<'a>(item: &'a Item, segment: &EditPathSegment) -> Option<&'a Item> {
+    match segment {
+        EditPathSegment::Key(key) => item.get(key.as_str()),
+        EditPathSegment::Index(index) => item.get(*index),
+    }
+}
+
+fn child_mut<'a>(item: &'a mut Item, segment: &EditPathSegment) -> Option<&'a mut Item> {
+    match segment {
+        EditPathSegment::Key(key) => item.get_mut(key.as_str()),
+        EditPathSegment::Index(index) => item.get_mut(*index),
+    }
+}
+
+fn item_to_lua(lua: &Lua, item: Item) -> LuaResult<LuaValue<'_>> {
+    match item.into_value() {
+        Ok(value) => {
+            let value = TomlValue::deserialize(value.into_deserializer()).into_lua_err()?;
+            lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)
+        }
+        Err(_) => Ok(LuaValue::Nil),
+    }
+}
+
+/**
+    Creates a new item for the given value.
+
+    Values inside of regular tables are written the same way as
+    `serde.encode` would write them, meaning tables become new
+    `[table]` sections, and arrays of tables become `[[array]]`
+    sections. Values inside of inline tables are always inline.
+*/
+fn new_item(in_table: bool, value: Value) -> Item {
+    if !in_table {
+        return Item::Value(value);
+    }
+    match value {
+        Value::InlineTable(table) => {
+            let mut table = table.into_table();
+            table.set_implicit(true);
+            Item::Table(table)
+        }
+        Value::Array(array) if !array.is_empty() && array.iter().all(Value::is_inline_table) => {
+            Item::Value(Value::Array(array))
+                .into_array_of_tables()
+                .map_or_else(|item| item, Item::ArrayOfTables)
+        }
+        value => Item::Value(value),
+    }
+}
+
+/**
+    Replaces an existing item with a new value, keeping
+    the formatting and any comments around the old value.
+*/
+fn replace_item(existing: &mut Item, mut value: Value) {
+    match existing {
+        Item::Value(old) => {
+            *value.decor_mut() = old.decor().clone();
+            *old = value;
+        }
+        Item::Table(_) | Item::ArrayOfTables(_) => *existing = new_item(true, value),
+        Item::None => *existing = Item::Value(value),
+    }
+}
@@ -0,0 +1,210 @@
+use std::cmp::Ordering;
+
+use mlua::prelude::*;
+
+use super::EncodeDecodeConfig;
+
+// NOTE: Lua tables may contain themselves, so we need to limit the depth
+// of nested tables to give a proper error instead of overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+/**
+    A value for the binary formats, MessagePack and CBOR.
+
+    These formats distinguish between some things that lua does not,
+    so lua values are always converted according to the rules below:
+
+    - Numbers that are whole and fit in 64 bits are integers, other numbers are floats
+    - Strings that are valid utf-8 are text strings, other strings are binary strings
+    - Tables with only sequential keys starting at 1 are arrays, other tables are maps
+    - Empty tables are maps, same as for the json format
+    - Tables that were decoded with the `preserveArrays` option are always arrays
+    - The `serde.null` sentinel is nil
+
+    Map keys are sorted to make the encoded output deterministic.
+
+    When decoding, integers, floats, text strings and binary
+    strings all become lua numbers and lua strings respectively,
+    and the `preserveNull`, `preserveArrays` and `largeIntegersAsStrings`
+    options are respected the same way as for the other formats.
+
+    Integers are stored as 128-bit integers, since both formats
+    can contain integers that do not fit in a signed 64-bit integer.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryValue {
+    Nil,
+    Boolean(bool),
+    Integer(i128),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Array(Vec<BinaryValue>),
+    Map(Vec<(BinaryValue, BinaryValue)>),
+}
+
+impl BinaryValue {
+    pub fn from_lua_value(lua: &Lua, value: LuaValue) -> LuaResult<Self> {
+        Self::from_lua_value_with_depth(lua, value, 0)
+    }
+
+    fn from_lua_value_with_depth(lua: &Lua, value: LuaValue, depth: usize) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::Nil,
+            LuaValue::LightUserData(ud) if ud.0.is_null() => Self::Nil,
+            LuaValue::Boolean(b) => Self::Boolean(b),
+            LuaValue::Integer(i) => Self::Integer(i.into()),
+            LuaValue::Number(n) => Self::from_number(n),
+            LuaValue::String(s) => match s.to_str() {
+                Ok(s) => Self::String(s.to_string()),
+                Err(_) => Self::Bytes(s.as_bytes().to_vec()),
+            },
+            LuaValue::Table(t) => {
+                if depth >= MAX_DEPTH {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Tables may be nested at most {MAX_DEPTH} levels deep, \
+                        or the table contains a reference to itself"
+                    )));
+                }
+                Self::from_lua_table(lua, t, depth + 1)?
+            }
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Unsupported value of type '{}', only nil, booleans, \
+                    numbers, strings and tables can be encoded",
+                    value.type_name()
+                )))
+            }
+        })
+    }
+
+    fn from_number(n: f64) -> Self {
+        // NOTE: i64::MAX can not be represented exactly as a float, so
+        // we have to use an exclusive upper bound of 2^63 for this check
+        if n.fract() == 0.0 && n >= i64::MIN as f64 && n < 9_223_372_036_854_775_808.0 {
+            Self::Integer((n as i64).into())
+        } else {
+            Self::Float(n)
+        }
+    }
+
+    fn from_lua_table(lua: &Lua, table: LuaTable, depth: usize) -> LuaResult<Self> {
+        let len = table.raw_len();
+
+        // NOTE: Tables with the array metatable are always arrays, even when
+        // empty, same as how the serde-based formats treat them when encoding
+        if table.get_metatable() == Some(lua.array_metatable()) {
+            let array = (1..=len)
+                .map(|index| Self::from_lua_value_with_depth(lua, table.raw_get(index)?, depth))
+                .collect::<LuaResult<Vec<_>>>()?;
+            return Ok(Self::Array(array));
+        }
+
+        let pairs = table
+            .clone()
+            .pairs::<LuaValue, LuaValue>()
+            .collect::<LuaResult<Vec<_>>>()?;
+
+        if len > 0 && pairs.len() == len {
+            let mut array = Vec::with_capacity(len);
+            for index in 1..=len {
+                match table.raw_get(index)? {
+                    LuaValue::Nil => break,
+                    value => array.push(Self::from_lua_value_with_depth(lua, value, depth)?),
+                }
+            }
+            if array.len() == len {
+                return Ok(Self::Array(array));
+            }
+        }
+
+        let mut map = pairs
+            .into_iter()
+            .map(|(key, value)| {
+                Ok((
+                    Self::from_lua_value_with_depth(lua, key, depth)?,
+                    Self::from_lua_value_with_depth(lua, value, depth)?,
+                ))
+            })
+            .collect::<LuaResult<Vec<_>>>()?;
+        map.sort_by(|(a, _), (b, _)| a.cmp_key(b));
+        Ok(Self::Map(map))
+    }
+
+    fn cmp_key(&self, other: &Self) -> Ordering {
+        fn rank(value: &BinaryValue) -> u8 {
+            match value {
+                BinaryValue::Nil => 0,
+                BinaryValue::Boolean(_) => 1,
+                BinaryValue::Integer(_) | BinaryValue::Float(_) => 2,
+                BinaryValue::String(_) | BinaryValue::Bytes(_) => 3,
+                BinaryValue::Array(_) => 4,
+                BinaryValue::Map(_) => 5,
+            }
+        }
+        match (self, other) {
+            (Self::Boolean(a), Self::Boolean(b)) => a.cmp(b),
+            (Self::Integer(a), Self::Integer(b)) => a.cmp(b),
+            (a, b) if rank(a) == 2 && rank(b) == 2 => a.as_f64().total_cmp(&b.as_f64()),
+            (a, b) if rank(a) == 3 && rank(b) == 3 => a.as_bytes().cmp(b.as_bytes()),
+            (a, b) => rank(a).cmp(&rank(b)),
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match self {
+            Self::Integer(i) => *i as f64,
+            Self::Float(f) => *f,
+            _ => f64::NAN,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::String(s) => s.as_bytes(),
+            Self::Bytes(b) => b,
+            _ => &[],
+        }
+    }
+
+    pub fn into_lua_value<'lua>(
+        self,
+        lua: &'lua Lua,
+        config: &EncodeDecodeConfig,
+    ) -> LuaResult<LuaValue<'lua>> {
+        Ok(match self {
+            Self::Nil if config.preserve_null => lua.null(),
+            Self::Nil => LuaValue::Nil,
+            Self::Boolean(b) => LuaValue::Boolean(b),
+            Self::Integer(i) => LuaValue::Number(i as f64),
+            Self::Float(f) => LuaValue::Number(f),
+            Self::String(s) => LuaValue::String(lua.create_string(s)?),
+            Self::Bytes(b) => LuaValue::String(lua.create_string(b)?),
+            Self::Array(array) => {
+                let table = lua.create_table_with_capacity(array.len(), 0)?;
+                for (index, value) in array.into_iter().enumerate() {
+                    table.raw_set(index + 1, value.into_lua_value(lua, config)?)?;
+                }
+                if config.preserve_arrays {
+                    table.set_metatable(Some(lua.array_metatable()));
+                }
+                LuaValue::Table(table)
+            }
+            Self::Map(map) => {
+                let table = lua.create_table_with_capacity(0, map.len())?;
+                for (key, value) in map {
+                    if key == Self::Nil {
+                        return Err(LuaError::RuntimeError(
+                            "Map keys can not be nil".to_string(),
+                        ));
+                    }
+                    table.raw_set(
+                        key.into_lua_value(lua, config)?,
+                        value.into_lua_value(lua, config)?,
+                    )?;
+                }
+                LuaValue::Table(table)
+            }
+        })
+    }
+}
@@ -0,0 +1,69 @@
+use ciborium::value::Value as CborValue;
+use mlua::prelude::*;
+
+use super::binary_value::BinaryValue;
+
+pub fn encode(value: BinaryValue) -> LuaResult<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(128);
+    ciborium::ser::into_writer(&to_cbor(value), &mut bytes).into_lua_err()?;
+    Ok(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> LuaResult<BinaryValue> {
+    let mut reader = bytes;
+    let value: CborValue = ciborium::de::from_reader(&mut reader).into_lua_err()?;
+    if !reader.is_empty() {
+        return Err(LuaError::RuntimeError(format!(
+            "CBOR contains {} trailing bytes after the encoded value",
+            reader.len()
+        )));
+    }
+    from_cbor(value)
+}
+
+fn to_cbor(value: BinaryValue) -> CborValue {
+    match value {
+        BinaryValue::Nil => CborValue::Null,
+        BinaryValue::Boolean(b) => CborValue::Bool(b),
+        BinaryValue::Integer(i) => match i.try_into() {
+            Ok(i) => CborValue::Integer(i),
+            Err(_) => CborValue::Float(i as f64),
+        },
+        BinaryValue::Float(f) => CborValue::Float(f),
+        BinaryValue::String(s) => CborValue::Text(s),
+        BinaryValue::Bytes(b) => CborValue::Bytes(b),
+        BinaryValue::Array(a) => CborValue::Array(a.into_iter().map(to_cbor).collect()),
+        BinaryValue::Map(m) => CborValue::Map(
+            m.into_iter()
+                .map(|(k, v)| (to_cbor(k), to_cbor(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn from_cbor(value: CborValue) -> LuaResult<BinaryValue> {
+    Ok(match value {
+        CborValue::Null => BinaryValue::Nil,
+        CborValue::Bool(b) => BinaryValue::Boolean(b),
+        CborValue::Integer(i) => BinaryValue::Integer(i.into()),
+        CborValue::Float(f) => BinaryValue::Float(f),
+        CborValue::Text(s) => BinaryValue::String(s),
+        CborValue::Bytes(b) => BinaryValue::Bytes(b),
+        CborValue::Array(a) => {
+            BinaryValue::Array(a.into_iter().map(from_cbor).collect::<LuaResult<_>>()?)
+        }
+        CborValue::Map(m) => BinaryValue::Map(
+            m.into_iter()
+                .map(|(k, v)| Ok((from_cbor(k)?, from_cbor(v)?)))
+                .collect::<LuaResult<_>>()?,
+        ),
+        // NOTE: Tags only give extra meaning to the value they contain,
+        // such as a timestamp, so we can safely decode just that value
+        CborValue::Tag(_, value) => from_cbor(*value)?,
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Unsupported CBOR value {value:?}"
+            )))
+        }
+    })
+}
@@ -0,0 +1,293 @@
+use std::{collections::BTreeSet, fs::File, io::Read};
+
+use csv::{ByteRecord, ReaderBuilder, WriterBuilder};
+use mlua::prelude::*;
+
+pub const DEFAULT_DELIMITER: u8 = b',';
+
+/**
+    How the header row of csv data should be handled.
+
+    - `false` means there is no header row, and rows are arrays of fields
+    - `true` means the first row is the header row, and rows are tables keyed by the header names
+    - An array of names is the same as `true`, but the given names are used instead of the
+      header row when decoding, and the header row is written in the given order when encoding
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CsvHeaders {
+    #[default]
+    None,
+    FirstRow,
+    Names(Vec<String>),
+}
+
+impl<'lua> FromLua<'lua> for CsvHeaders {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil | LuaValue::Boolean(false) => Ok(Self::None),
+            LuaValue::Boolean(true) => Ok(Self::FirstRow),
+            LuaValue::Table(tab) => Ok(Self::Names(
+                tab.clone()
+                    .sequence_values::<String>()
+                    .collect::<LuaResult<_>>()?,
+            )),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "CsvHeaders",
+                message: Some(format!(
+                    "Invalid headers - expected boolean or table of names, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    Parses a csv delimiter from a string containing a single character.
+
+    Quotes and newlines are not valid delimiters since they
+    already have a special meaning in the csv format itself.
+*/
+pub fn parse_delimiter(value: &LuaValue) -> LuaResult<u8> {
+    let bytes = match value {
+        LuaValue::String(s) => s.as_bytes(),
+        _ => &[],
+    };
+    match bytes {
+        [byte] if byte.is_ascii() && !matches!(byte, b'"' | b'\r' | b'\n') => Ok(*byte),
+        _ => Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "CsvDelimiter",
+            message: Some(
+                "Invalid delimiter - expected a single character \
+                that is not a quote or newline"
+                    .to_string(),
+            ),
+        }),
+    }
+}
+
+/**
+    Reads csv rows one at a time, converting them into lua tables.
+
+    Rows are read in a streaming fashion, so this
+    can be used for files much larger than memory.
+*/
+pub struct CsvReader<R: Read> {
+    reader: csv::Reader<R>,
+    headers: Option<Vec<Vec<u8>>>,
+    record: ByteRecord,
+}
+
+impl<R: Read> CsvReader<R> {
+    pub fn new(reader: R, delimiter: u8, headers: &CsvHeaders) -> LuaResult<Self> {
+        // NOTE: We handle the header row ourselves, since the csv crate
+        // would otherwise return it as an error for empty data, and the
+        // reader is flexible since rows without headers may differ in length
+        let mut reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader);
+        let mut record = ByteRecord::new();
+        let headers = match headers {
+            CsvHeaders::None => None,
+            CsvHeaders::FirstRow | CsvHeaders::Names(_) => {
+                if !reader.read_byte_record(&mut record).map_err(csv_error)? {
+                    Some(Vec::new())
+                } else if let CsvHeaders::Names(names) = headers {
+                    if names.len() != record.len() {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Expected {} header names, but the header row has {} fields",
+                            names.len(),
+                            record.len()
+                        )));
+                    }
+                    Some(names.iter().map(|name| name.as_bytes().to_vec()).collect())
+                } else {
+                    Some(record.iter().map(<[u8]>::to_vec).collect())
+                }
+            }
+        };
+        Ok(Self {
+            reader,
+            headers,
+            record,
+        })
+    }
+
+    pub fn next_row<'lua>(&mut self, lua: &'lua Lua) -> LuaResult<Option<LuaTable<'lua>>> {
+        if !self
+            .reader
+            .read_byte_record(&mut self.record)
+            .map_err(csv_error)?
+        {
+            return Ok(None);
+        }
+        let row = lua.create_table_with_capacity(self.record.len(), 0)?;
+        match &self.headers {
+            None => {
+                for (index, field) in self.record.iter().enumerate() {
+                    row.raw_set(index + 1, lua.create_string(field)?)?;
+                }
+            }
+            Some(headers) => {
+                if headers.len() != self.record.len() {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Invalid csv data - row on line {} has {} fields, but the header row has {}",
+                        self.record.position().map_or(0, |pos| pos.line()),
+                        self.record.len(),
+                        headers.len()
+                    )));
+                }
+                for (header, field) in headers.iter().zip(self.record.iter()) {
+                    row.raw_set(lua.create_string(header)?, lua.create_string(field)?)?;
+                }
+            }
+        }
+        Ok(Some(row))
+    }
+}
+
+pub fn encode(
+    lua: &Lua,
+    value: LuaValue,
+    delimiter: u8,
+    headers: &CsvHeaders,
+) -> LuaResult<Vec<u8>> {
+    let rows = match value {
+        LuaValue::Table(rows) => (1..=rows.raw_len())
+            .map(|index| rows.raw_get::<_, LuaValue>(index))
+            .collect::<LuaResult<Vec<_>>>()?,
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Expected a table of rows to encode as csv, got {}",
+                value.type_name()
+            )))
+        }
+    };
+    let rows = rows
+        .into_iter()
+        .enumerate()
+        .map(|(index, row)| match row {
+            LuaValue::Table(row) => Ok(row),
+            row => Err(LuaError::RuntimeError(format!(
+                "Expected row {} to be a table, got {}",
+                index + 1,
+                row.type_name()
+            ))),
+        })
+        .collect::<LuaResult<Vec<_>>>()?;
+
+    let names = match headers {
+        CsvHeaders::None => None,
+        CsvHeaders::FirstRow => Some(collect_header_names(&rows)?),
+        CsvHeaders::Names(names) => Some(names.clone()),
+    };
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_writer(Vec::new());
+    if let Some(names) = &names {
+        writer.write_record(names).into_lua_err()?;
+    }
+
+    let mut record = ByteRecord::new();
+    for row in rows {
+        record.clear();
+        match &names {
+            None => {
+                for index in 1..=row.raw_len() {
+                    push_field(lua, &mut record, row.raw_get(index)?)?;
+                }
+            }
+            Some(names) => {
+                for name in names {
+                    push_field(lua, &mut record, row.raw_get(name.as_str())?)?;
+                }
+            }
+        }
+        writer.write_byte_record(&record).into_lua_err()?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| LuaError::RuntimeError(e.error().to_string()))
+}
+
+pub fn decode<'lua>(
+    lua: &'lua Lua,
+    bytes: &[u8],
+    delimiter: u8,
+    headers: &CsvHeaders,
+) -> LuaResult<LuaValue<'lua>> {
+    let mut reader = CsvReader::new(bytes, delimiter, headers)?;
+    let rows = lua.create_table()?;
+    while let Some(row) = reader.next_row(lua)? {
+        rows.raw_push(row)?;
+    }
+    Ok(LuaValue::Table(rows))
+}
+
+pub fn rows<'lua>(
+    lua: &'lua Lua,
+    path: String,
+    delimiter: u8,
+    headers: &CsvHeaders,
+) -> LuaResult<LuaFunction<'lua>> {
+    let file = File::open(path).into_lua_err()?;
+    let mut reader = CsvReader::new(file, delimiter, headers)?;
+    lua.create_function_mut(move |lua, _: ()| reader.next_row(lua))
+}
+
+fn collect_header_names(rows: &[LuaTable]) -> LuaResult<Vec<String>> {
+    // NOTE: Lua tables have no order, so the header
+    // names are sorted to make the output deterministic
+    let mut names = BTreeSet::new();
+    for row in rows {
+        for pair in row.clone().pairs::<LuaValue, LuaValue>() {
+            match pair?.0 {
+                LuaValue::String(s) => names.insert(s.to_str()?.to_string()),
+                key => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Expected only string keys in rows when encoding csv with headers, got {}",
+                        key.type_name()
+                    )))
+                }
+            };
+        }
+    }
+    Ok(names.into_iter().collect())
+}
+
+fn push_field(lua: &Lua, record: &mut ByteRecord, value: LuaValue) -> LuaResult<()> {
+    match value {
+        LuaValue::Nil => record.push_field(b""),
+        LuaValue::Boolean(b) => record.push_field(b.to_string().as_bytes()),
+        LuaValue::Integer(_) | LuaValue::Number(_) | LuaValue::String(_) => {
+            let type_name = value.type_name();
+            match lua.coerce_string(value)? {
+                Some(s) => record.push_field(s.as_bytes()),
+                None => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Failed to convert {type_name} into a csv field"
+                    )))
+                }
+            }
+        }
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Unsupported value of type '{}' in csv field, only nil, \
+                booleans, numbers and strings can be encoded",
+                value.type_name()
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn csv_error(e: csv::Error) -> LuaError {
+    LuaError::RuntimeError(format!("Invalid csv data - {e}"))
+}
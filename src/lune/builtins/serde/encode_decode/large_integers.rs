@@ -0,0 +1,94 @@
+use serde_json::Value as JsonValue;
+use serde_yaml::Value as YamlValue;
+use toml::Value as TomlValue;
+
+use super::binary_value::BinaryValue;
+
+// NOTE: Lua numbers are 64-bit floats, which can only represent
+// integers up to 2^53 exactly, any larger integers lose precision
+const MAX_SAFE_INTEGER: u64 = 1 << 53;
+
+fn is_safe_integer(signed: Option<i64>, unsigned: Option<u64>) -> bool {
+    match (signed, unsigned) {
+        (Some(i), _) => i.unsigned_abs() <= MAX_SAFE_INTEGER,
+        (None, Some(u)) => u <= MAX_SAFE_INTEGER,
+        (None, None) => true, // Floats are never converted
+    }
+}
+
+/**
+    Replaces integers that can not be represented exactly
+    as lua numbers with strings containing their digits.
+*/
+pub trait LargeIntegersToStrings {
+    fn large_integers_to_strings(&mut self);
+}
+
+impl LargeIntegersToStrings for JsonValue {
+    fn large_integers_to_strings(&mut self) {
+        match self {
+            JsonValue::Number(n) if !is_safe_integer(n.as_i64(), n.as_u64()) => {
+                *self = JsonValue::String(n.to_string());
+            }
+            JsonValue::Array(array) => array
+                .iter_mut()
+                .for_each(LargeIntegersToStrings::large_integers_to_strings),
+            JsonValue::Object(object) => object
+                .values_mut()
+                .for_each(LargeIntegersToStrings::large_integers_to_strings),
+            _ => {}
+        }
+    }
+}
+
+impl LargeIntegersToStrings for YamlValue {
+    fn large_integers_to_strings(&mut self) {
+        match self {
+            YamlValue::Number(n) if !is_safe_integer(n.as_i64(), n.as_u64()) => {
+                *self = YamlValue::String(n.to_string());
+            }
+            YamlValue::Sequence(sequence) => sequence
+                .iter_mut()
+                .for_each(LargeIntegersToStrings::large_integers_to_strings),
+            YamlValue::Mapping(mapping) => mapping
+                .values_mut()
+                .for_each(LargeIntegersToStrings::large_integers_to_strings),
+            YamlValue::Tagged(tagged) => tagged.value.large_integers_to_strings(),
+            _ => {}
+        }
+    }
+}
+
+impl LargeIntegersToStrings for TomlValue {
+    fn large_integers_to_strings(&mut self) {
+        match self {
+            TomlValue::Integer(i) if !is_safe_integer(Some(*i), None) => {
+                *self = TomlValue::String(i.to_string());
+            }
+            TomlValue::Array(array) => array
+                .iter_mut()
+                .for_each(LargeIntegersToStrings::large_integers_to_strings),
+            TomlValue::Table(table) => table
+                .iter_mut()
+                .for_each(|(_, value)| value.large_integers_to_strings()),
+            _ => {}
+        }
+    }
+}
+
+impl LargeIntegersToStrings for BinaryValue {
+    fn large_integers_to_strings(&mut self) {
+        match self {
+            BinaryValue::Integer(i) if i.unsigned_abs() > u128::from(MAX_SAFE_INTEGER) => {
+                *self = BinaryValue::String(i.to_string());
+            }
+            BinaryValue::Array(array) => array
+                .iter_mut()
+                .for_each(LargeIntegersToStrings::large_integers_to_strings),
+            BinaryValue::Map(map) => map
+                .iter_mut()
+                .for_each(|(_, value)| value.large_integers_to_strings()),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine as _,
+};
+use data_encoding::{BASE32, BASE32_NOPAD};
+use jsonc_parser::{parse_to_serde_value, ParseOptions};
+use mlua::prelude::*;
+
+use serde::Serialize;
+use serde_json::{ser::PrettyFormatter, Value as JsonValue};
+use serde_yaml::Value as YamlValue;
+use toml::Value as TomlValue;
+
+mod binary_value;
+mod cbor;
+mod csv;
+mod large_integers;
+mod msgpack;
+mod ndjson;
+
+use binary_value::BinaryValue;
+use large_integers::LargeIntegersToStrings;
+
+pub use self::csv::CsvHeaders;
+pub use self::ndjson::NdjsonDecoder;
+
+pub const LUA_SERIALIZE_OPTIONS: LuaSerializeOptions = LuaSerializeOptions::new()
+    .set_array_metatable(false)
+    .serialize_none_to_null(false)
+    .serialize_unit_to_null(false);
+
+pub const LUA_DESERIALIZE_OPTIONS: LuaDeserializeOptions = LuaDeserializeOptions::new()
+    .sort_keys(true)
+    .deny_recursive_tables(false)
+    .deny_unsupported_types(true);
+
+#[derive(Debug, Clone, Copy)]
+pub enum EncodeDecodeFormat {
+    Json,
+    Jsonc,
+    Json5,
+    Ndjson,
+    Yaml,
+    Toml,
+    Msgpack,
+    Cbor,
+    Csv,
+    Base64,
+    Base64Url,
+    Base32,
+    Hex,
+}
+
+impl EncodeDecodeFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Jsonc => "jsonc",
+            Self::Json5 => "json5",
+            Self::Ndjson => "ndjson",
+            Self::Yaml => "yaml",
+            Self::Toml => "toml",
+            Self::Msgpack => "msgpack",
+            Self::Cbor => "cbor",
+            Self::Csv => "csv",
+            Self::Base64 => "base64",
+            Self::Base64Url => "base64url",
+            Self::Base32 => "base32",
+            Self::Hex => "hex",
+        }
+    }
+
+    pub fn is_binary_encoding(&self) -> bool {
+        matches!(
+            self,
+            Self::Base64 | Self::Base64Url | Self::Base32 | Self::Hex
+        )
+    }
+}
+
+impl<'lua> FromLua<'lua> for EncodeDecodeFormat {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "json" => Ok(Self::Json),
+                "jsonc" => Ok(Self::Jsonc),
+                "json5" => Ok(Self::Json5),
+                "ndjson" | "jsonl" => Ok(Self::Ndjson),
+                "yaml" => Ok(Self::Yaml),
+                "toml" => Ok(Self::Toml),
+                "msgpack" => Ok(Self::Msgpack),
+                "cbor" => Ok(Self::Cbor),
+                "csv" => Ok(Self::Csv),
+                "base64" => Ok(Self::Base64),
+                "base64url" => Ok(Self::Base64Url),
+                "base32" => Ok(Self::Base32),
+                "hex" => Ok(Self::Hex),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "EncodeDecodeFormat",
+                    message: Some(format!(
+                        "Invalid format '{kind}', valid formats are:  \
+                        json, jsonc, json5, ndjson, jsonl, yaml, toml, msgpack, cbor, csv, base64, base64url, base32, hex"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "EncodeDecodeFormat",
+                message: None,
+            })
+        }
+    }
+}
+
+/**
+    Options for encoding and decoding.
+
+    May be given from lua as a boolean, which is the same as
+    setting only the `pretty` option, or as a table of options.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct EncodeDecodeOptions {
+    pub pretty: bool,
+    pub indent: Option<usize>,
+    pub sort_keys: Option<bool>,
+    pub preserve_null: bool,
+    pub preserve_arrays: bool,
+    pub large_integers_as_strings: bool,
+    pub padding: Option<bool>,
+    pub delimiter: Option<u8>,
+    pub headers: CsvHeaders,
+}
+
+impl<'lua> FromLua<'lua> for EncodeDecodeOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Boolean(pretty) => Ok(Self {
+                pretty: *pretty,
+                ..Default::default()
+            }),
+            LuaValue::Table(tab) => Ok(Self {
+                pretty: tab
+                    .raw_get::<_, Option<bool>>("pretty")?
+                    .unwrap_or_default(),
+                indent: tab.raw_get("indent")?,
+                sort_keys: tab.raw_get("sortKeys")?,
+                preserve_null: tab
+                    .raw_get::<_, Option<bool>>("preserveNull")?
+                    .unwrap_or_default(),
+                preserve_arrays: tab
+                    .raw_get::<_, Option<bool>>("preserveArrays")?
+                    .unwrap_or_default(),
+                large_integers_as_strings: tab
+                    .raw_get::<_, Option<bool>>("largeIntegersAsStrings")?
+                    .unwrap_or_default(),
+                padding: tab.raw_get("padding")?,
+                delimiter: match tab.raw_get::<_, LuaValue>("delimiter")? {
+                    LuaValue::Nil => None,
+                    value => Some(csv::parse_delimiter(&value)?),
+                },
+                headers: tab.raw_get("headers")?,
+            }),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "EncodeDecodeOptions",
+                message: Some(format!(
+                    "Invalid options - expected boolean, table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EncodeDecodeConfig {
+    pub format: EncodeDecodeFormat,
+    pub pretty: bool,
+    pub indent: Option<usize>,
+    pub sort_keys: bool,
+    pub preserve_null: bool,
+    pub preserve_arrays: bool,
+    pub large_integers_as_strings: bool,
+    pub padding: Option<bool>,
+    pub delimiter: u8,
+    pub headers: CsvHeaders,
+}
+
+impl EncodeDecodeConfig {
+    pub fn serialize_to_string<'lua>(
+        self,
+        lua: &'lua Lua,
+        value: LuaValue<'lua>,
+    ) -> LuaResult<LuaString<'lua>> {
+        if self.format.is_binary_encoding() {
+            return match value {
+                LuaValue::String(s) => lua.create_string(self.encode_bytes(s.as_bytes())),
+                value => Err(LuaError::RuntimeError(format!(
+                    "Expected a string to encode as {}, got {}",
+                    self.format.name(),
+                    value.type_name()
+                ))),
+            };
+        }
+        let bytes = match self.format {
+            // NOTE: Plain json is also valid jsonc and json5, so
+            // encoding uses the same (strict) json serializer
+            EncodeDecodeFormat::Json | EncodeDecodeFormat::Jsonc | EncodeDecodeFormat::Json5 => {
+                let serialized: JsonValue =
+                    lua.from_value_with(value, self.deserialize_options())?;
+                if let Some(indent) = self.indent {
+                    let indent = " ".repeat(indent);
+                    let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+                    let mut writer = Vec::with_capacity(128);
+                    let mut serializer =
+                        serde_json::Serializer::with_formatter(&mut writer, formatter);
+                    serialized.serialize(&mut serializer).into_lua_err()?;
+                    writer
+                } else if self.pretty {
+                    serde_json::to_vec_pretty(&serialized).into_lua_err()?
+                } else {
+                    serde_json::to_vec(&serialized).into_lua_err()?
+                }
+            }
+            EncodeDecodeFormat::Ndjson => ndjson::encode(lua, value, self.deserialize_options())?,
+            EncodeDecodeFormat::Yaml => {
+                let serialized: YamlValue =
+                    lua.from_value_with(value, self.deserialize_options())?;
+                let mut writer = Vec::with_capacity(128);
+                serde_yaml::to_writer(&mut writer, &serialized).into_lua_err()?;
+                writer
+            }
+            EncodeDecodeFormat::Toml => {
+                let serialized: TomlValue =
+                    lua.from_value_with(value, self.deserialize_options())?;
+                let s = if self.pretty {
+                    toml::to_string_pretty(&serialized).into_lua_err()?
+                } else {
+                    toml::to_string(&serialized).into_lua_err()?
+                };
+                s.as_bytes().to_vec()
+            }
+            EncodeDecodeFormat::Msgpack => {
+                msgpack::encode(BinaryValue::from_lua_value(lua, value)?)?
+            }
+            EncodeDecodeFormat::Cbor => cbor::encode(BinaryValue::from_lua_value(lua, value)?)?,
+            EncodeDecodeFormat::Csv => csv::encode(lua, value, self.delimiter, &self.headers)?,
+            _ => unreachable!("binary encodings are handled above"),
+        };
+        lua.create_string(bytes)
+    }
+
+    pub fn deserialize_from_string<'lua>(
+        self,
+        lua: &'lua Lua,
+        string: LuaString<'lua>,
+    ) -> LuaResult<LuaValue<'lua>> {
+        let bytes = string.as_bytes();
+        if self.format.is_binary_encoding() {
+            let decoded = self.decode_bytes(bytes).map_err(|e| {
+                LuaError::RuntimeError(format!("Invalid {} data - {e}", self.format.name()))
+            })?;
+            return lua.create_string(decoded).map(LuaValue::String);
+        }
+        match self.format {
+            EncodeDecodeFormat::Json => {
+                let value: JsonValue = serde_json::from_slice(bytes).into_lua_err()?;
+                self.value_to_lua(lua, value)
+            }
+            EncodeDecodeFormat::Jsonc | EncodeDecodeFormat::Json5 => {
+                let s = string.to_str().map_err(|_| {
+                    LuaError::RuntimeError(format!("{} must be valid utf-8", self.format.name()))
+                })?;
+                let value: JsonValue = parse_to_serde_value(s, &self.json_parse_options())
+                    .map_err(|e| {
+                        LuaError::RuntimeError(format!("Invalid {} data - {e}", self.format.name()))
+                    })?;
+                self.value_to_lua(lua, value)
+            }
+            EncodeDecodeFormat::Ndjson => ndjson::decode(lua, bytes, self.clone()),
+            EncodeDecodeFormat::Yaml => {
+                let value: YamlValue = serde_yaml::from_slice(bytes).into_lua_err()?;
+                self.value_to_lua(lua, value)
+            }
+            EncodeDecodeFormat::Toml => {
+                if let Ok(s) = string.to_str() {
+                    let value: TomlValue = toml::from_str(s).into_lua_err()?;
+                    self.value_to_lua(lua, value)
+                } else {
+                    Err(LuaError::RuntimeError(
+                        "TOML must be valid utf-8".to_string(),
+                    ))
+                }
+            }
+            EncodeDecodeFormat::Msgpack => self.binary_value_to_lua(lua, msgpack::decode(bytes)?),
+            EncodeDecodeFormat::Cbor => self.binary_value_to_lua(lua, cbor::decode(bytes)?),
+            EncodeDecodeFormat::Csv => csv::decode(lua, bytes, self.delimiter, &self.headers),
+            _ => unreachable!("binary encodings are handled above"),
+        }
+    }
+
+    fn value_to_lua<'lua, T>(&self, lua: &'lua Lua, mut value: T) -> LuaResult<LuaValue<'lua>>
+    where
+        T: Serialize + LargeIntegersToStrings,
+    {
+        if self.large_integers_as_strings {
+            value.large_integers_to_strings();
+        }
+        let options = LUA_SERIALIZE_OPTIONS
+            .set_array_metatable(self.preserve_arrays)
+            .serialize_none_to_null(self.preserve_null)
+            .serialize_unit_to_null(self.preserve_null);
+        lua.to_value_with(&value, options)
+    }
+
+    fn binary_value_to_lua<'lua>(
+        &self,
+        lua: &'lua Lua,
+        mut value: BinaryValue,
+    ) -> LuaResult<LuaValue<'lua>> {
+        if self.large_integers_as_strings {
+            value.large_integers_to_strings();
+        }
+        value.into_lua_value(lua, self)
+    }
+
+    fn deserialize_options(&self) -> LuaDeserializeOptions {
+        LUA_DESERIALIZE_OPTIONS.sort_keys(self.sort_keys)
+    }
+
+    /**
+        Options for parsing the json supersets.
+
+        Jsonc only adds comments and trailing commas, which is what editors
+        such as VS Code accept, while json5 also allows unquoted keys,
+        single-quoted strings, hexadecimal numbers, and more.
+    */
+    fn json_parse_options(&self) -> ParseOptions {
+        match self.format {
+            EncodeDecodeFormat::Json5 => ParseOptions::default(),
+            _ => ParseOptions {
+                allow_comments: true,
+                allow_trailing_commas: true,
+                allow_loose_object_property_names: false,
+                allow_missing_commas: false,
+                allow_single_quoted_strings: false,
+                allow_hexadecimal_numbers: false,
+                allow_unary_plus_numbers: false,
+                allow_bare_decimal_point_numbers: false,
+                allow_non_finite_numbers: false,
+                allow_extended_string_escapes: false,
+            },
+        }
+    }
+
+    /**
+        Creates an iterator function that reads csv rows from
+        the file at the given path, one row for each call.
+    */
+    pub fn csv_rows<'lua>(self, lua: &'lua Lua, path: String) -> LuaResult<LuaFunction<'lua>> {
+        csv::rows(lua, path, self.delimiter, &self.headers)
+    }
+
+    fn encode_bytes(&self, bytes: &[u8]) -> Vec<u8> {
+        // NOTE: Padding is included by default when encoding, since
+        // that is what the standard variants of these encodings use
+        let padding = self.padding.unwrap_or(true);
+        match self.format {
+            EncodeDecodeFormat::Base64 => self.base64_engine(&alphabet::STANDARD).encode(bytes),
+            EncodeDecodeFormat::Base64Url => self.base64_engine(&alphabet::URL_SAFE).encode(bytes),
+            EncodeDecodeFormat::Base32 if padding => BASE32.encode(bytes),
+            EncodeDecodeFormat::Base32 => BASE32_NOPAD.encode(bytes),
+            EncodeDecodeFormat::Hex => hex::encode(bytes),
+            _ => unreachable!("only binary encodings can encode bytes"),
+        }
+        .into_bytes()
+    }
+
+    fn decode_bytes(&self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        // NOTE: Padding is optional by default when decoding, and only
+        // required or disallowed when the padding option was given
+        match self.format {
+            EncodeDecodeFormat::Base64 => self
+                .base64_engine(&alphabet::STANDARD)
+                .decode(bytes)
+                .map_err(|e| e.to_string()),
+            EncodeDecodeFormat::Base64Url => self
+                .base64_engine(&alphabet::URL_SAFE)
+                .decode(bytes)
+                .map_err(|e| e.to_string()),
+            EncodeDecodeFormat::Base32 => {
+                let padded = match self.padding {
+                    Some(padding) => padding,
+                    None => bytes.ends_with(b"="),
+                };
+                match padded {
+                    true => BASE32.decode(bytes),
+                    false => BASE32_NOPAD.decode(bytes),
+                }
+                .map_err(|e| e.to_string())
+            }
+            EncodeDecodeFormat::Hex => hex::decode(bytes).map_err(|e| e.to_string()),
+            _ => unreachable!("only binary encodings can decode bytes"),
+        }
+    }
+
+    fn base64_engine(&self, alphabet: &alphabet::Alphabet) -> GeneralPurpose {
+        let config = GeneralPurposeConfig::new()
+            .with_encode_padding(self.padding.unwrap_or(true))
+            .with_decode_padding_mode(match self.padding {
+                None => DecodePaddingMode::Indifferent,
+                Some(true) => DecodePaddingMode::RequireCanonical,
+                Some(false) => DecodePaddingMode::RequireNone,
+            });
+        GeneralPurpose::new(alphabet, config)
+    }
+}
+
+impl From<EncodeDecodeFormat> for EncodeDecodeConfig {
+    fn from(format: EncodeDecodeFormat) -> Self {
+        Self {
+            format,
+            pretty: false,
+            indent: None,
+            sort_keys: true,
+            preserve_null: false,
+            preserve_arrays: false,
+            large_integers_as_strings: false,
+            padding: None,
+            delimiter: csv::DEFAULT_DELIMITER,
+            headers: CsvHeaders::None,
+        }
+    }
+}
+
+impl From<(EncodeDecodeFormat, bool)> for EncodeDecodeConfig {
+    fn from(value: (EncodeDecodeFormat, bool)) -> Self {
+        Self {
+            pretty: value.1,
+            ..Self::from(value.0)
+        }
+    }
+}
+
+impl From<(EncodeDecodeFormat, EncodeDecodeOptions)> for EncodeDecodeConfig {
+    fn from(value: (EncodeDecodeFormat, EncodeDecodeOptions)) -> Self {
+        let (format, options) = value;
+        Self {
+            format,
+            pretty: options.pretty,
+            indent: options.indent,
+            sort_keys: options.sort_keys.unwrap_or(true),
+            preserve_null: options.preserve_null,
+            preserve_arrays: options.preserve_arrays,
+            large_integers_as_strings: options.large_integers_as_strings,
+            padding: options.padding,
+            delimiter: options.delimiter.unwrap_or(csv::DEFAULT_DELIMITER),
+            headers: options.headers,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use mlua::prelude::*;
+use rmpv::Value as MsgpackValue;
+
+use super::binary_value::BinaryValue;
+
+pub fn encode(value: BinaryValue) -> LuaResult<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(128);
+    rmpv::encode::write_value(&mut bytes, &to_msgpack(value)).into_lua_err()?;
+    Ok(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> LuaResult<BinaryValue> {
+    let mut reader = bytes;
+    let value = rmpv::decode::read_value(&mut reader).into_lua_err()?;
+    if !reader.is_empty() {
+        return Err(LuaError::RuntimeError(format!(
+            "MessagePack contains {} trailing bytes after the encoded value",
+            reader.len()
+        )));
+    }
+    from_msgpack(value)
+}
+
+fn to_msgpack(value: BinaryValue) -> MsgpackValue {
+    match value {
+        BinaryValue::Nil => MsgpackValue::Nil,
+        BinaryValue::Boolean(b) => MsgpackValue::Boolean(b),
+        BinaryValue::Integer(i) => match (i64::try_from(i), u64::try_from(i)) {
+            (Ok(i), _) => MsgpackValue::from(i),
+            (_, Ok(u)) => MsgpackValue::from(u),
+            _ => MsgpackValue::F64(i as f64),
+        },
+        BinaryValue::Float(f) => MsgpackValue::F64(f),
+        BinaryValue::String(s) => MsgpackValue::from(s),
+        BinaryValue::Bytes(b) => MsgpackValue::Binary(b),
+        BinaryValue::Array(a) => MsgpackValue::Array(a.into_iter().map(to_msgpack).collect()),
+        BinaryValue::Map(m) => MsgpackValue::Map(
+            m.into_iter()
+                .map(|(k, v)| (to_msgpack(k), to_msgpack(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn from_msgpack(value: MsgpackValue) -> LuaResult<BinaryValue> {
+    Ok(match value {
+        MsgpackValue::Nil => BinaryValue::Nil,
+        MsgpackValue::Boolean(b) => BinaryValue::Boolean(b),
+        MsgpackValue::Integer(i) => match (i.as_i64(), i.as_u64()) {
+            (Some(i), _) => BinaryValue::Integer(i.into()),
+            (_, Some(u)) => BinaryValue::Integer(u.into()),
+            _ => BinaryValue::Float(i.as_f64().unwrap_or(f64::NAN)),
+        },
+        MsgpackValue::F32(f) => BinaryValue::Float(f as f64),
+        MsgpackValue::F64(f) => BinaryValue::Float(f),
+        MsgpackValue::String(s) => BinaryValue::Bytes(s.into_bytes()),
+        MsgpackValue::Binary(b) => BinaryValue::Bytes(b),
+        MsgpackValue::Array(a) => {
+            BinaryValue::Array(a.into_iter().map(from_msgpack).collect::<LuaResult<_>>()?)
+        }
+        MsgpackValue::Map(m) => BinaryValue::Map(
+            m.into_iter()
+                .map(|(k, v)| Ok((from_msgpack(k)?, from_msgpack(v)?)))
+                .collect::<LuaResult<_>>()?,
+        ),
+        MsgpackValue::Ext(kind, _) => {
+            return Err(LuaError::RuntimeError(format!(
+                "Unsupported MessagePack extension type {kind}"
+            )))
+        }
+    })
+}
@@ -0,0 +1,117 @@
+use mlua::prelude::*;
+use serde_json::Value as JsonValue;
+
+use super::EncodeDecodeConfig;
+
+/**
+    Encodes a sequence of values as newline-delimited json,
+    with one compact json value on each line.
+*/
+pub fn encode(lua: &Lua, value: LuaValue, options: LuaDeserializeOptions) -> LuaResult<Vec<u8>> {
+    let LuaValue::Table(tab) = value else {
+        return Err(LuaError::RuntimeError(format!(
+            "Expected an array of values to encode as ndjson, got {}",
+            value.type_name()
+        )));
+    };
+    let mut writer = Vec::with_capacity(128);
+    for value in tab.sequence_values::<LuaValue>() {
+        let serialized: JsonValue = lua.from_value_with(value?, options)?;
+        serde_json::to_writer(&mut writer, &serialized).into_lua_err()?;
+        writer.push(b'\n');
+    }
+    Ok(writer)
+}
+
+/**
+    Decodes newline-delimited json into an array of values.
+*/
+pub fn decode<'lua>(
+    lua: &'lua Lua,
+    bytes: &[u8],
+    config: EncodeDecodeConfig,
+) -> LuaResult<LuaValue<'lua>> {
+    let mut decoder = NdjsonDecoder::new(config);
+    decoder.buffer.extend_from_slice(bytes);
+    decoder.decode_buffered(lua, true)
+}
+
+/**
+    Incrementally decodes newline-delimited json.
+
+    Chunks may be pushed in as they arrive, and may split lines at any point.
+    Partial lines are buffered until the rest of the line has been pushed,
+    or until the decoder is finished, whichever comes first.
+*/
+pub struct NdjsonDecoder {
+    config: EncodeDecodeConfig,
+    buffer: Vec<u8>,
+    scanned: usize,
+    line: usize,
+}
+
+impl NdjsonDecoder {
+    pub fn new(config: EncodeDecodeConfig) -> Self {
+        Self {
+            config,
+            buffer: Vec::new(),
+            scanned: 0,
+            line: 0,
+        }
+    }
+
+    fn decode_buffered<'lua>(&mut self, lua: &'lua Lua, finish: bool) -> LuaResult<LuaValue<'lua>> {
+        // NOTE: Complete lines are decoded in place and the consumed prefix is
+        // drained once per call, and bytes that were already scanned for a
+        // newline are not scanned again when more chunks are pushed, so that
+        // decoding stays linear even when lines arrive in many small chunks
+        let mut values = Vec::new();
+        let mut start = 0;
+        let mut result = Ok(());
+        while let Some(offset) = self.buffer[self.scanned..].iter().position(|b| *b == b'\n') {
+            let line = start..self.scanned + offset + 1;
+            start = line.end;
+            self.scanned = line.end;
+            self.line += 1;
+            match decode_line(&self.buffer[line], self.line) {
+                Ok(value) => values.extend(value),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.buffer.drain(..start);
+        self.scanned = self.buffer.len();
+        result?;
+        if finish {
+            let line = std::mem::take(&mut self.buffer);
+            self.scanned = 0;
+            self.line += 1;
+            values.extend(decode_line(&line, self.line)?);
+        }
+        self.config.value_to_lua(lua, JsonValue::Array(values))
+    }
+}
+
+fn decode_line(line: &[u8], number: usize) -> LuaResult<Option<JsonValue>> {
+    // NOTE: Blank lines are skipped, and trailing carriage returns are
+    // whitespace to the json parser, so crlf line endings also work
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    serde_json::from_slice(line)
+        .map(Some)
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid ndjson data on line {number} - {e}")))
+}
+
+impl LuaUserData for NdjsonDecoder {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("push", |lua, this, chunk: LuaString| {
+            this.buffer.extend_from_slice(chunk.as_bytes());
+            this.decode_buffered(lua, false)
+        });
+
+        methods.add_method_mut("finish", |lua, this, ()| this.decode_buffered(lua, true));
+    }
+}
@@ -1,12 +1,18 @@
 use mlua::prelude::*;
 
+pub(super) mod archive;
 pub(super) mod compress_decompress;
 pub(super) mod crypto;
+pub(super) mod edit;
 pub(super) mod encode_decode;
+pub(super) mod validate;
 
-use compress_decompress::{compress, decompress, CompressDecompressFormat};
-use crypto::Crypto;
-use encode_decode::{EncodeDecodeConfig, EncodeDecodeFormat};
+use compress_decompress::{
+    compress, decompress, decompress_detected, CompressDecompressFormat, CompressDecompressOptions,
+    CompressDecompressStream,
+};
+use edit::{EditFormat, EditableDocument};
+use encode_decode::{EncodeDecodeConfig, EncodeDecodeFormat, EncodeDecodeOptions, NdjsonDecoder};
 
 use crate::lune::util::TableBuilder;
 
@@ -14,71 +20,123 @@ pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
     TableBuilder::new(lua)?
         .with_function("encode", serde_encode)?
         .with_function("decode", serde_decode)?
+        .with_function("csvRows", serde_csv_rows)?
+        .with_function("ndjsonDecoder", serde_ndjson_decoder)?
+        .with_function("edit", serde_edit)?
+        .with_function("validate", serde_validate)?
+        .with_value("null", lua.null())?
         .with_async_function("compress", serde_compress)?
         .with_async_function("decompress", serde_decompress)?
-        .with_value(
-            "crypto",
-            TableBuilder::new(lua)?
-                .with_function("sha1", |_, content: Option<String>| {
-                    Ok(Crypto::sha1(content))
-                })?
-                .with_function("sha256", |_, content: Option<String>| {
-                    Ok(Crypto::sha256(content))
-                })?
-                .with_function("sha512", |_, content: Option<String>| {
-                    Ok(Crypto::sha512(content))
-                })?
-                .with_function("md5", |_, content: Option<String>| Ok(Crypto::md5(content)))?
-                .with_function("blake2s256", |_, content: Option<String>| {
-                    Ok(Crypto::blake2s256(content))
-                })?
-                .with_function("blake2b512", |_, content: Option<String>| {
-                    Ok(Crypto::blake2b512(content))
-                })?
-                .with_function("sha3", |_, (variant, content): (String, Option<String>)| {
-                    match variant.to_string().as_str() {
-                        "256" => Ok(Crypto::sha3_256(content)),
-                        "512" => Ok(Crypto::sha3_512(content)),
-
-                        &_ => Err(LuaError::runtime(format!(
-                            "Expected sha3 variant to be 256-bit or 512-bit, got {}",
-                            variant
-                        ))),
-                    }
-                })?
-                .build()?,
-        )?
+        .with_function("detectCompression", serde_detect_compression)?
+        .with_function("compressor", serde_compressor)?
+        .with_function("decompressor", serde_decompressor)?
+        .with_value("archive", archive::create(lua)?)?
+        .with_value("crypto", crypto::create(lua)?)?
         .build_readonly()
 }
 
 fn serde_encode<'lua>(
     lua: &'lua Lua,
-    (format, val, pretty): (EncodeDecodeFormat, LuaValue<'lua>, Option<bool>),
+    (format, val, options): (EncodeDecodeFormat, LuaValue<'lua>, EncodeDecodeOptions),
 ) -> LuaResult<LuaString<'lua>> {
-    let config = EncodeDecodeConfig::from((format, pretty.unwrap_or_default()));
+    let config = EncodeDecodeConfig::from((format, options));
     config.serialize_to_string(lua, val)
 }
 
 fn serde_decode<'lua>(
     lua: &'lua Lua,
-    (format, str): (EncodeDecodeFormat, LuaString<'lua>),
+    (format, str, options): (EncodeDecodeFormat, LuaString<'lua>, EncodeDecodeOptions),
 ) -> LuaResult<LuaValue<'lua>> {
-    let config = EncodeDecodeConfig::from(format);
+    let config = EncodeDecodeConfig::from((format, options));
     config.deserialize_from_string(lua, str)
 }
 
+fn serde_csv_rows<'lua>(
+    lua: &'lua Lua,
+    (path, options): (String, EncodeDecodeOptions),
+) -> LuaResult<LuaFunction<'lua>> {
+    let config = EncodeDecodeConfig::from((EncodeDecodeFormat::Csv, options));
+    config.csv_rows(lua, path)
+}
+
+fn serde_ndjson_decoder(_: &Lua, options: EncodeDecodeOptions) -> LuaResult<NdjsonDecoder> {
+    let config = EncodeDecodeConfig::from((EncodeDecodeFormat::Ndjson, options));
+    Ok(NdjsonDecoder::new(config))
+}
+
+fn serde_edit(_: &Lua, (format, text): (EditFormat, String)) -> LuaResult<EditableDocument> {
+    EditableDocument::parse(format, &text)
+}
+
+fn serde_validate<'lua>(
+    lua: &'lua Lua,
+    (schema, value): (LuaValue<'lua>, LuaValue<'lua>),
+) -> LuaResult<LuaTable<'lua>> {
+    validate::validate(lua, schema, value)
+}
+
 async fn serde_compress<'lua>(
     lua: &'lua Lua,
-    (format, str): (CompressDecompressFormat, LuaString<'lua>),
+    (format, str, options): (
+        CompressDecompressFormat,
+        LuaString<'lua>,
+        CompressDecompressOptions,
+    ),
 ) -> LuaResult<LuaString<'lua>> {
-    let bytes = compress(format, str).await?;
+    let bytes = compress(format, str, options).await?;
     lua.create_string(bytes)
 }
 
 async fn serde_decompress<'lua>(
     lua: &'lua Lua,
-    (format, str): (CompressDecompressFormat, LuaString<'lua>),
+    (format, str): (LuaValue<'lua>, Option<LuaString<'lua>>),
 ) -> LuaResult<LuaString<'lua>> {
-    let bytes = decompress(format, str).await?;
+    // NOTE: The format may be omitted entirely, in which case
+    // the only argument given is the string to decompress, but
+    // a lone format name is much more likely to be a mistake
+    let bytes = match (format, str) {
+        (LuaValue::String(str), None) => {
+            if str == "auto"
+                || CompressDecompressFormat::from_lua(LuaValue::String(str.clone()), lua).is_ok()
+            {
+                return Err(LuaError::RuntimeError(format!(
+                    "Missing the string to decompress - only the format '{}' was given",
+                    str.to_string_lossy()
+                )));
+            }
+            decompress_detected(str).await?
+        }
+        (LuaValue::String(format), Some(str)) if format == "auto" => {
+            decompress_detected(str).await?
+        }
+        (format, Some(str)) => {
+            let format = CompressDecompressFormat::from_lua(format, lua)?;
+            decompress(format, str).await?
+        }
+        (format, None) => {
+            return Err(LuaError::RuntimeError(format!(
+                "Expected a string to decompress, got {}",
+                format.type_name()
+            )))
+        }
+    };
     lua.create_string(bytes)
 }
+
+fn serde_detect_compression(_: &Lua, str: LuaString) -> LuaResult<Option<&'static str>> {
+    Ok(CompressDecompressFormat::detect_from_bytes(str).map(|format| format.name()))
+}
+
+fn serde_compressor(
+    _: &Lua,
+    (format, options): (CompressDecompressFormat, CompressDecompressOptions),
+) -> LuaResult<CompressDecompressStream> {
+    CompressDecompressStream::compressor(format, options)
+}
+
+fn serde_decompressor(
+    _: &Lua,
+    format: CompressDecompressFormat,
+) -> LuaResult<CompressDecompressStream> {
+    CompressDecompressStream::decompressor(format)
+}
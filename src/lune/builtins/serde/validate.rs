@@ -0,0 +1,47 @@
+use jsonschema::ValidationError;
+use mlua::prelude::*;
+use serde_json::Value as JsonValue;
+
+use crate::lune::util::TableBuilder;
+
+use super::encode_decode::LUA_DESERIALIZE_OPTIONS;
+
+/**
+    Validates a lua value against a json schema.
+
+    Both the schema and the value are converted into json values first, the same way
+    that `serde.encode` would convert them, which means that values decoded from any
+    format can be validated, as long as they can also be represented as json.
+
+    Schemas without a `$schema` keyword are treated as draft 2020-12 schemas.
+*/
+pub fn validate<'lua>(
+    lua: &'lua Lua,
+    schema: LuaValue<'lua>,
+    value: LuaValue<'lua>,
+) -> LuaResult<LuaTable<'lua>> {
+    let schema: JsonValue = lua.from_value_with(schema, LUA_DESERIALIZE_OPTIONS)?;
+    let value: JsonValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+
+    let validator = jsonschema::validator_for(&schema)
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid schema - {e}")))?;
+
+    let errors = validator
+        .iter_errors(&value)
+        .map(|error| error_to_table(lua, &error))
+        .collect::<LuaResult<Vec<_>>>()?;
+
+    TableBuilder::new(lua)?
+        .with_value("valid", errors.is_empty())?
+        .with_value("errors", lua.create_sequence_from(errors)?)?
+        .build()
+}
+
+fn error_to_table<'lua>(lua: &'lua Lua, error: &ValidationError) -> LuaResult<LuaTable<'lua>> {
+    TableBuilder::new(lua)?
+        .with_value("path", error.instance_path().as_str())?
+        .with_value("schemaPath", error.schema_path().as_str())?
+        .with_value("keyword", error.kind().keyword())?
+        .with_value("message", error.to_string())?
+        .build()
+}
@@ -5,7 +5,7 @@ use tokio::{
     task,
 };
 
-use super::{IntoLuaThread, Scheduler};
+use super::{IntoLuaThread, Scheduler, SchedulerFuture};
 
 impl<'fut> Scheduler<'fut> {
     /**
@@ -23,10 +23,34 @@ impl<'fut> Scheduler<'fut> {
                 .try_lock()
                 .expect("Failed to lock background futures for check")
                 .len()
-                > 0,
+                > 0
+                || !self
+                    .futures_background_pending
+                    .try_lock()
+                    .expect("Failed to lock pending background futures for check")
+                    .is_empty(),
         )
     }
 
+    /**
+        Pushes a future onto the background futures queue.
+
+        The background futures queue is locked while background futures
+        are being resumed, and a lua future may spawn background futures
+        during that time, so we queue those futures up separately and
+        move them to the background futures queue on next resumption.
+    */
+    fn push_background(&self, fut: SchedulerFuture<'static>) {
+        match self.futures_background.try_lock() {
+            Ok(futs) => futs.push(fut),
+            Err(_) => self
+                .futures_background_pending
+                .try_lock()
+                .expect("Failed to lock pending futures queue for background tasks")
+                .push(fut),
+        }
+    }
+
     /**
         Schedules a plain future to run in the background.
 
@@ -53,11 +77,7 @@ impl<'fut> Scheduler<'fut> {
 
         // NOTE: We must spawn a future on our scheduler which awaits
         // the handle from tokio to start driving our future properly
-        let futs = self
-            .futures_background
-            .try_lock()
-            .expect("Failed to lock futures queue for background tasks");
-        futs.push(Box::pin(async move {
+        self.push_background(Box::pin(async move {
             handle.await.ok();
         }));
 
@@ -79,11 +99,7 @@ impl<'fut> Scheduler<'fut> {
     {
         let (tx, rx) = oneshot::channel();
 
-        let futs = self
-            .futures_background
-            .try_lock()
-            .expect("Failed to lock futures queue for background tasks");
-        futs.push(Box::pin(async move {
+        self.push_background(Box::pin(async move {
             let res = fut.await;
             tx.send(res).ok();
         }));
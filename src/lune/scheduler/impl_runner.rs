@@ -1,4 +1,4 @@
-use std::{process::ExitCode, sync::Arc};
+use std::{mem, process::ExitCode, sync::Arc};
 
 use futures_util::StreamExt;
 use mlua::prelude::*;
@@ -114,6 +114,19 @@ impl<'fut> Scheduler<'fut> {
             .futures_background
             .try_lock()
             .expect("Failed to lock background futures for resumption");
+
+        // Move any futures that were spawned during the last
+        // resumption into the queue, see `push_background`
+        let pending = mem::take(
+            &mut *self
+                .futures_background_pending
+                .try_lock()
+                .expect("Failed to lock pending background futures for resumption"),
+        );
+        if !pending.is_empty() {
+            futs.push(Box::pin(pending.for_each(|_| async {})));
+        }
+
         assert!(futs.len() > 0, "No background futures are queued");
         futs.next().await;
     }
@@ -61,6 +61,7 @@ pub(crate) struct Scheduler<'fut> {
     */
     futures_lua: Arc<AsyncMutex<FuturesUnordered<SchedulerFuture<'fut>>>>,
     futures_background: Arc<AsyncMutex<FuturesUnordered<SchedulerFuture<'static>>>>,
+    futures_background_pending: Arc<AsyncMutex<FuturesUnordered<SchedulerFuture<'static>>>>,
 }
 
 impl<'fut> Scheduler<'fut> {
@@ -75,6 +76,7 @@ impl<'fut> Scheduler<'fut> {
             thread_senders: Arc::new(AsyncMutex::new(HashMap::new())),
             futures_lua: Arc::new(AsyncMutex::new(FuturesUnordered::new())),
             futures_background: Arc::new(AsyncMutex::new(FuturesUnordered::new())),
+            futures_background_pending: Arc::new(AsyncMutex::new(FuturesUnordered::new())),
         }
     }
 
@@ -59,14 +59,21 @@ create_tests! {
     luau_load: "luau/load",
     luau_options: "luau/options",
 
+    net_client_cookies: "net/client/cookies",
+    net_client_options: "net/client/options",
     net_request_codes: "net/request/codes",
     net_request_compression: "net/request/compression",
+    net_request_form: "net/request/form",
     net_request_methods: "net/request/methods",
+    net_request_options: "net/request/options",
     net_request_query: "net/request/query",
     net_request_redirect: "net/request/redirect",
+    net_request_response: "net/request/response",
+    net_request_stream: "net/request/stream",
     net_url_encode: "net/url/encode",
     net_url_decode: "net/url/decode",
     net_serve_requests: "net/serve/requests",
+    net_serve_stop: "net/serve/stop",
     net_serve_websockets: "net/serve/websockets",
     net_socket_wss: "net/socket/wss",
     net_socket_wss_rw: "net/socket/wss_rw",
@@ -104,12 +111,44 @@ create_tests! {
     global_typeof: "globals/typeof",
     global_warn: "globals/warn",
 
+    serde_archive_malformed: "serde/archive/malformed",
+    serde_archive_roundtrip: "serde/archive/roundtrip",
+    serde_archive_traversal: "serde/archive/traversal",
+    serde_cbor_encode: "serde/cbor/encode",
+    serde_cbor_roundtrip: "serde/cbor/roundtrip",
+    serde_compression_detect: "serde/compression/detect",
     serde_compression_files: "serde/compression/files",
+    serde_compression_options: "serde/compression/options",
     serde_compression_roundtrip: "serde/compression/roundtrip",
+    serde_compression_stream: "serde/compression/stream",
+    serde_crypto_binary: "serde/crypto/binary",
+    serde_crypto_cipher: "serde/crypto/cipher",
+    serde_crypto_errors: "serde/crypto/errors",
+    serde_crypto_families: "serde/crypto/families",
+    serde_crypto_file: "serde/crypto/file",
+    serde_crypto_hmac: "serde/crypto/hmac",
+    serde_crypto_kdf: "serde/crypto/kdf",
+    serde_crypto_keypair: "serde/crypto/keypair",
+    serde_crypto_random: "serde/crypto/random",
+    serde_csv_decode: "serde/csv/decode",
+    serde_csv_encode: "serde/csv/encode",
+    serde_csv_rows: "serde/csv/rows",
+    serde_edit_json: "serde/edit/json",
+    serde_edit_toml: "serde/edit/toml",
+    serde_encoding_roundtrip: "serde/encoding/roundtrip",
+    serde_encoding_vectors: "serde/encoding/vectors",
     serde_json_decode: "serde/json/decode",
     serde_json_encode: "serde/json/encode",
+    serde_json_options: "serde/json/options",
+    serde_jsonc_decode: "serde/jsonc/decode",
+    serde_msgpack_encode: "serde/msgpack/encode",
+    serde_msgpack_roundtrip: "serde/msgpack/roundtrip",
+    serde_ndjson_decoder: "serde/ndjson/decoder",
+    serde_ndjson_roundtrip: "serde/ndjson/roundtrip",
     serde_toml_decode: "serde/toml/decode",
     serde_toml_encode: "serde/toml/encode",
+    serde_validate_errors: "serde/validate/errors",
+    serde_validate_formats: "serde/validate/formats",
 
     stdio_format: "stdio/format",
     stdio_color: "stdio/color",